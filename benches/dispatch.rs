@@ -0,0 +1,79 @@
+//! Measures allocations and time per `run_all` and `run_all_unchecked` call, and per `run_all` pruning a dropped
+//! handler.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const HANDLERS: usize = 64;
+const ITERATIONS: usize = 10_000;
+
+/// Runs `f` once per input, reporting the allocations and time per call.
+///
+/// The inputs are built up front so that allocating them isn't counted.
+fn measure<I>(name: &str, inputs: Vec<I>, mut f: impl FnMut(I)) {
+    let calls = inputs.len();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for input in inputs {
+        f(input);
+    }
+    let elapsed = started.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{name}: {:.2} allocations/call, {:?}/call",
+        allocations as f64 / calls as f64,
+        elapsed / calls as u32,
+    );
+}
+
+fn main() {
+    let mut cb_manager = CallbackManager::<i32>::new();
+    let _handlers = (0..HANDLERS)
+        .map(|_| cb_manager.add(CallbackHandler::Callback1(Box::new(|x| { black_box(x); }))).unwrap())
+        .collect::<Vec<_>>();
+
+    let params = vec![CallbackParams::CallParams1(1); HANDLERS];
+    measure("run_all", vec![params.clone(); ITERATIONS], |params| {
+        cb_manager.run_all(params).unwrap();
+    });
+
+    measure("run_all_unchecked", vec![(); ITERATIONS], |()| {
+        cb_manager.run_all_unchecked(&params).unwrap();
+    });
+
+    let mut mismatching_params = params.clone();
+    mismatching_params[HANDLERS / 2] = CallbackParams::CallParams0();
+    measure("run_all with a mismatching param", vec![mismatching_params; ITERATIONS], |params| {
+        cb_manager.run_all(params).unwrap_err();
+    });
+
+    // Also counts the allocation of the handler's `Arc` by `add`.
+    measure("add, drop and run_all pruning the dropped handler", vec![params.clone(); ITERATIONS], |params| {
+        drop(cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+        cb_manager.run_all(params).unwrap();
+    });
+}
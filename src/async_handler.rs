@@ -0,0 +1,209 @@
+//! Handlers returning futures, awaited one after another. Available with the `async` feature.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::entries::{self, Entry};
+use crate::sync::{self, Mutex};
+use crate::{ArityMismatch, CallbackError, CallbackParams, HandlerId};
+
+/// The future returned by an [`AsyncCallbackHandler`].
+pub type BoxFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+macro_rules! async_handlers {
+    ($($variant:ident, $params:ident, $arity:literal, ($($p:ident: $t:ident),*);)*) => {
+        /// Enumeration of handlers returning a future, registered with an [`AsyncCallbackManager`].
+        #[allow(clippy::type_complexity)]
+        pub enum AsyncCallbackHandler<'a, T: 'a> {
+            $($variant(Box<dyn FnMut($($t),*) -> BoxFuture<'a> + Send + 'a>),)*
+        }
+
+        impl<'a, T: 'a> AsyncCallbackHandler<'a, T> {
+            /// Returns the number of arguments the handler takes.
+            pub const fn arity(&self) -> usize {
+                match self {
+                    $(AsyncCallbackHandler::$variant(_) => $arity,)*
+                }
+            }
+        }
+
+        impl<'a, T: Copy + 'a> AsyncCallbackHandler<'a, T> {
+            /// Invokes the handler with the params, returning its future, or `None` if the arities differ.
+            fn call(&mut self, params: &CallbackParams<T>) -> Option<BoxFuture<'a>> {
+                match (self, params) {
+                    $((AsyncCallbackHandler::$variant(handler), CallbackParams::$params($($p),*)) => Some(handler($(*$p),*)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+async_handlers! {
+    Callback0, CallParams0, 0, ();
+    Callback1, CallParams1, 1, (p1: T);
+    Callback2, CallParams2, 2, (p1: T, p2: T);
+    Callback3, CallParams3, 3, (p1: T, p2: T, p3: T);
+    Callback4, CallParams4, 4, (p1: T, p2: T, p3: T, p4: T);
+    Callback5, CallParams5, 5, (p1: T, p2: T, p3: T, p4: T, p5: T);
+    Callback6, CallParams6, 6, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T);
+    Callback7, CallParams7, 7, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T);
+    Callback8, CallParams8, 8, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T);
+    Callback9, CallParams9, 9, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T);
+    Callback10, CallParams10, 10, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T);
+    Callback11, CallParams11, 11, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T);
+    Callback12, CallParams12, 12, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T, p12: T);
+}
+
+/// A manager of [`AsyncCallbackHandler`]s, the async counterpart to [`CallbackManager`](crate::CallbackManager).
+///
+/// As with `CallbackManager`, the manager only holds weak references: a handler stays registered as long as the
+/// handle returned by `add` is alive.
+pub struct AsyncCallbackManager<'a, T: 'a> {
+    handlers: Vec<Entry<AsyncCallbackHandler<'a, T>>>,
+    next_id: u64,
+}
+
+impl<'a, T: 'a> Default for AsyncCallbackManager<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: 'a> AsyncCallbackManager<'a, T> {
+    /// Creates a new `AsyncCallbackManager` instance.
+    pub fn new() -> Self {
+        Self { handlers: vec![], next_id: 0 }
+    }
+
+    /// Adds a new async handler, returning the strong handle keeping it registered.
+    pub fn add(&mut self, handler: AsyncCallbackHandler<'a, T>) -> Arc<Mutex<AsyncCallbackHandler<'a, T>>> {
+        let strong_handler = Arc::new(Mutex::new(handler));
+        self.handlers.push((HandlerId(self.next_id), Arc::downgrade(&strong_handler)));
+        self.next_id += 1;
+        strong_handler
+    }
+
+    /// Returns the number of handlers still alive.
+    pub fn active_count(&self) -> usize {
+        self.handlers.iter().filter(|(_, handler)| handler.strong_count() > 0).count()
+    }
+}
+
+impl<'a, T: Copy + 'a> AsyncCallbackManager<'a, T> {
+    /// Runs all active handlers with specific parameter lists, awaiting each handler's future before invoking the
+    /// next one, so handlers complete in registration order.
+    ///
+    /// As with `CallbackManager::run_all`, the params are checked against the handlers before any is invoked. A
+    /// handler's mutex is only held while invoking it to get its future, not while the future is awaited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::future::Future;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::task::{Context, Poll, Waker};
+    /// use callback_manager::{AsyncCallbackHandler, AsyncCallbackManager, CallbackParams};
+    ///
+    /// // A minimal executor polling the future on the current thread, any async runtime works as well.
+    /// fn block_on<F: Future>(future: F) -> F::Output {
+    ///     let mut future = std::pin::pin!(future);
+    ///     let mut context = Context::from_waker(Waker::noop());
+    ///     loop {
+    ///         if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+    ///             return output;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut cb_manager = AsyncCallbackManager::<i32>::new();
+    /// let output = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let output_clone = Arc::clone(&output);
+    /// let _handler = cb_manager.add(AsyncCallbackHandler::Callback1(Box::new(move |x| {
+    ///     let output = Arc::clone(&output_clone);
+    ///     Box::pin(async move { output.lock().unwrap().push(x) })
+    /// })));
+    ///
+    /// assert!(block_on(cb_manager.run_all_async(vec![CallbackParams::CallParams1(1)])).is_ok());
+    /// assert_eq!(*output.lock().unwrap(), vec![1]);
+    /// ```
+    pub async fn run_all_async(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        entries::prune_and_check(&mut self.handlers, &params, AsyncCallbackHandler::arity)?;
+
+        for (index, ((id, handler), param)) in self.handlers.iter().zip(&params).enumerate() {
+            let mutex_handler = handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+            let future = {
+                let mut guard_handler = sync::lock(&mutex_handler)?;
+                let arity = guard_handler.arity();
+                guard_handler.call(param).ok_or_else(|| CallbackError::ParamTypeMismatch {
+                    mismatches: vec![ArityMismatch { index, id: *id, expected: arity, got: param.arity() }],
+                })?
+            };
+            future.await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AsyncCallbackHandler, AsyncCallbackManager, CallbackError, CallbackParams};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    /// Polls `future` to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// Returns `Pending` once before completing, so that other futures could run in between.
+    async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(|_| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            Poll::Pending
+        }).await
+    }
+
+    #[test]
+    fn test_run_all_async() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = AsyncCallbackManager::<i32>::new();
+        let handlers = (0..2).map(|i| {
+            let output = Arc::clone(&output);
+            callback_manager.add(AsyncCallbackHandler::Callback1(Box::new(move |x| {
+                let output = Arc::clone(&output);
+                Box::pin(async move {
+                    output.lock().unwrap().push(format!("start {i}: {x}"));
+                    yield_now().await;
+                    output.lock().unwrap().push(format!("end {i}"));
+                })
+            })))
+        }).collect::<Vec<_>>();
+
+        block_on(callback_manager.run_all_async(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)])).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec!["start 0: 1", "end 0", "start 1: 2", "end 1"]);
+        assert_eq!(
+            block_on(callback_manager.run_all_async(vec![CallbackParams::CallParams0(), CallbackParams::CallParams1(3)])).err(),
+            Some(CallbackError::ParamTypeMismatch {
+                mismatches: vec![crate::ArityMismatch { index: 0, id: crate::HandlerId(0), expected: 1, got: 0 }],
+            })
+        );
+        drop(handlers);
+        assert_eq!(callback_manager.active_count(), 0);
+    }
+}
@@ -0,0 +1,143 @@
+//! Dispatches queued by handlers, run once the current dispatch finishes.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::{CallbackError, CallbackManager, CallbackParams};
+
+/// A queue of `run_all` calls for [`CallbackManager::run_all_with_dispatcher`] to run after the current dispatch.
+///
+/// A handler can't dispatch on its own manager, as the manager is borrowed for the whole dispatch. Handlers capture
+/// a clone of the dispatcher instead and `queue` params on it, and the queued dispatches run once the outer one
+/// returns. Clones share the same queue.
+pub struct DeferredDispatcher<T> {
+    queue: Arc<Mutex<VecDeque<Vec<CallbackParams<T>>>>>,
+    limit: usize,
+}
+
+impl<T> DeferredDispatcher<T> {
+    /// The number of deferred dispatches `run_all_with_dispatcher` runs by default before giving up.
+    pub const DEFAULT_LIMIT: usize = 64;
+
+    /// Creates a dispatcher with an empty queue and `DEFAULT_LIMIT`.
+    pub fn new() -> Self {
+        Self::with_limit(Self::DEFAULT_LIMIT)
+    }
+
+    /// Creates a dispatcher with an empty queue, allowing `limit` deferred dispatches per `run_all_with_dispatcher`
+    /// call.
+    pub fn with_limit(limit: usize) -> Self {
+        Self { queue: Arc::new(Mutex::new(VecDeque::new())), limit }
+    }
+
+    /// Queues a `run_all` call with `params`, run after the dispatches queued before it.
+    pub fn queue(&self, params: Vec<CallbackParams<T>>) {
+        self.queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner).push_back(params);
+    }
+
+    /// Returns the number of dispatches waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner).len()
+    }
+
+    /// Returns whether no dispatch is waiting in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn pop(&self) -> Option<Vec<CallbackParams<T>>> {
+        self.queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner).pop_front()
+    }
+
+    fn clear(&self) {
+        self.queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clear();
+    }
+}
+
+impl<T> Clone for DeferredDispatcher<T> {
+    fn clone(&self) -> Self {
+        Self { queue: Arc::clone(&self.queue), limit: self.limit }
+    }
+}
+
+impl<T> Default for DeferredDispatcher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: Copy + 'a> CallbackManager<'a, T> {
+    /// Runs all active callback handlers like `run_all`, then runs the dispatches queued on `dispatcher`.
+    ///
+    /// Queued dispatches run in FIFO order, each one after the previous returned, so params queued by a deferred
+    /// dispatch run after everything queued before them. Dispatches queued before this call, for instance during a
+    /// plain `run_all`, run first.
+    ///
+    /// To stop handlers from queueing each other forever, at most the dispatcher's limit of deferred dispatches
+    /// run per call; past it, the queue is cleared and `CallbackError::DeferredLimitExceeded` is returned. The
+    /// queue is also cleared if a dispatch fails, and its error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, DeferredDispatcher};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let dispatcher = DeferredDispatcher::new();
+    /// let output = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let (output_clone, dispatcher_clone) = (Arc::clone(&output), dispatcher.clone());
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(move |x| {
+    ///     output_clone.lock().unwrap().push(x);
+    ///     if x > 0 {
+    ///         dispatcher_clone.queue(vec![CallbackParams::CallParams1(x - 1)]);
+    ///     }
+    /// }))).unwrap();
+    ///
+    /// assert!(cb_manager.run_all_with_dispatcher(vec![CallbackParams::CallParams1(2)], &dispatcher).is_ok());
+    /// assert_eq!(*output.lock().unwrap(), vec![2, 1, 0]);
+    /// ```
+    pub fn run_all_with_dispatcher(&mut self, params: Vec<CallbackParams<T>>, dispatcher: &DeferredDispatcher<T>) -> Result<(), CallbackError> {
+        let mut result = self.run_all(params);
+        let mut deferred = 0;
+        while result.is_ok() {
+            let Some(params) = dispatcher.pop() else {
+                return Ok(());
+            };
+            if deferred == dispatcher.limit {
+                result = Err(CallbackError::DeferredLimitExceeded { limit: dispatcher.limit });
+                break;
+            }
+            deferred += 1;
+            result = self.run_all(params);
+        }
+        dispatcher.clear();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams, DeferredDispatcher};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_run_all_with_dispatcher() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let dispatcher = DeferredDispatcher::with_limit(3);
+        let (output1, dispatcher1) = (Arc::clone(&output), dispatcher.clone());
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| {
+            output1.lock().unwrap().push(x);
+            dispatcher1.queue(vec![CallbackParams::CallParams1(x * 10)]);
+            dispatcher1.queue(vec![CallbackParams::CallParams1(x * 10 + 1)]);
+        }))).unwrap();
+
+        let result = callback_manager.run_all_with_dispatcher(vec![CallbackParams::CallParams1(1)], &dispatcher);
+
+        assert_eq!(result, Err(CallbackError::DeferredLimitExceeded { limit: 3 }));
+        assert_eq!(*output.lock().unwrap(), vec![1, 10, 11, 100]);
+        assert!(dispatcher.is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+//! The handler list shared by the managers of other handler kinds, like `ReturningCallbackManager`.
+
+use std::sync::Weak;
+
+use crate::sync::{self, Mutex};
+use crate::{ArityMismatch, CallbackError, CallbackParams, HandlerId};
+
+/// A registered handler and its id.
+pub(crate) type Entry<H> = (HandlerId, Weak<Mutex<H>>);
+
+/// Removes the dropped handlers, then checks `params` against the remaining ones, one param per handler, using
+/// `arity` to get the number of arguments each handler takes.
+///
+/// Fails with `CallbackError::ParamCountMismatch` or `CallbackError::ParamTypeMismatch` like
+/// `CallbackManager::run_all`, so that nothing is invoked for malformed params.
+pub(crate) fn prune_and_check<H, T>(
+    handlers: &mut Vec<Entry<H>>,
+    params: &[CallbackParams<T>],
+    arity: impl Fn(&H) -> usize,
+) -> Result<(), CallbackError> {
+    handlers.retain(|(_, handler)| handler.strong_count() > 0);
+    if params.len() != handlers.len() {
+        return Err(CallbackError::ParamCountMismatch { expected: handlers.len(), got: params.len() });
+    }
+    let mut mismatches = vec![];
+    for (index, ((id, handler), param)) in handlers.iter().zip(params).enumerate() {
+        let mutex_handler = handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+        let expected = arity(&*sync::lock(&mutex_handler)?);
+        if expected != param.arity() {
+            mismatches.push(ArityMismatch { index, id: *id, expected, got: param.arity() });
+        }
+    }
+    if !mismatches.is_empty() {
+        return Err(CallbackError::ParamTypeMismatch { mismatches });
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Dispatching user-defined event types through a manager.
+
+use std::sync::Arc;
+
+use crate::sync::Mutex;
+use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+
+type MapperFn<'a, E, T> = Box<dyn Fn(&E) -> Option<CallbackParams<T>> + Send + 'a>;
+
+/// A manager whose handlers are dispatched by emitting events of type `E`, usually a user enum.
+///
+/// Each handler is registered with `on` together with a mapper converting an event into its params. Emitting an
+/// event invokes the handlers whose mappers return params for it, in registration order.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use callback_manager::{CallbackHandler, CallbackParams, TypedDispatcher};
+///
+/// enum Event {
+///     Click { x: i32, y: i32 },
+///     Key(i32),
+/// }
+///
+/// let mut dispatcher = TypedDispatcher::<Event, i32>::new();
+/// let clicks = Arc::new(Mutex::new(vec![]));
+///
+/// let clicks_clone = Arc::clone(&clicks);
+/// let _handler = dispatcher.on(
+///     |event| match event {
+///         Event::Click { x, y } => Some(CallbackParams::CallParams2(*x, *y)),
+///         _ => None,
+///     },
+///     CallbackHandler::Callback2(Box::new(move |x, y| clicks_clone.lock().unwrap().push((x, y)))),
+/// ).unwrap();
+///
+/// dispatcher.emit(&Event::Click { x: 1, y: 2 }).unwrap();
+/// dispatcher.emit(&Event::Key(3)).unwrap();
+///
+/// assert_eq!(*clicks.lock().unwrap(), vec![(1, 2)]);
+/// ```
+pub struct TypedDispatcher<'a, E, T: 'a> {
+    manager: CallbackManager<'a, T>,
+    mappers: Vec<(HandlerId, MapperFn<'a, E, T>)>,
+}
+
+impl<'a, E, T: 'a> Default for TypedDispatcher<'a, E, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, E, T: 'a> TypedDispatcher<'a, E, T> {
+    /// Creates a new `TypedDispatcher` instance with an empty manager.
+    pub fn new() -> Self {
+        Self { manager: CallbackManager::new(), mappers: vec![] }
+    }
+
+    /// Adds a handler invoked with the params `mapper` returns for each emitted event, and not invoked for events
+    /// it returns `None` for.
+    ///
+    /// As with `CallbackManager::add`, the handler stays registered only while the returned handle is alive.
+    pub fn on(
+        &mut self,
+        mapper: impl Fn(&E) -> Option<CallbackParams<T>> + Send + 'a,
+        handler: CallbackHandler<'a, T>,
+    ) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let (id, handler) = self.manager.add_with_id(handler)?;
+        self.mappers.push((id, Box::new(mapper)));
+        Ok(handler)
+    }
+
+    /// Returns the underlying manager.
+    pub fn manager(&self) -> &CallbackManager<'a, T> {
+        &self.manager
+    }
+}
+
+impl<'a, E, T: Copy + 'a> TypedDispatcher<'a, E, T> {
+    /// Invokes the handlers whose mappers return params for `event`, like `CallbackManager::run_some`.
+    ///
+    /// Fails without invoking anything if a mapper returns params of another arity than its handler.
+    pub fn emit(&mut self, event: &E) -> Result<(), CallbackError> {
+        let entries = self.manager.handlers.iter().chain(&self.manager.catch_all);
+        self.mappers.retain(|(id, _)| entries.clone().any(|entry| entry.id == *id && entry.handler.strong_count() > 0));
+        let (ids, params) = self.mappers.iter().filter_map(
+            |(id, mapper)| Some((*id, mapper(event)?))
+        ).unzip::<_, _, Vec<HandlerId>, Vec<CallbackParams<T>>>();
+        self.manager.run_some(&ids, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArityMismatch, CallbackError, CallbackHandler, CallbackParams, HandlerId, TypedDispatcher};
+    use std::sync::{Arc, Mutex};
+
+    enum Event {
+        Move(i32, i32),
+        Scroll(i32),
+    }
+
+    #[test]
+    fn test_typed_dispatcher() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut dispatcher = TypedDispatcher::<Event, i32>::new();
+        let output_move = Arc::clone(&output);
+        let h0 = dispatcher.on(
+            |event| match event {
+                Event::Move(x, y) => Some(CallbackParams::CallParams2(*x, *y)),
+                Event::Scroll(_) => None,
+            },
+            CallbackHandler::Callback2(Box::new(move |x, y| output_move.lock().unwrap().push(x + y))),
+        ).unwrap();
+        let output_any = Arc::clone(&output);
+        let _h1 = dispatcher.on(
+            |event| Some(CallbackParams::CallParams1(match event {
+                Event::Move(..) => 0,
+                Event::Scroll(delta) => *delta,
+            })),
+            CallbackHandler::Callback1(Box::new(move |x| output_any.lock().unwrap().push(x))),
+        ).unwrap();
+
+        dispatcher.emit(&Event::Move(1, 2)).unwrap();
+        dispatcher.emit(&Event::Scroll(5)).unwrap();
+        drop(h0);
+        dispatcher.emit(&Event::Move(3, 4)).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![3, 0, 5, 0]);
+
+        let _h2 = dispatcher.on(|_event| Some(CallbackParams::CallParams0()), CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        assert_eq!(dispatcher.emit(&Event::Scroll(1)), Err(CallbackError::ParamTypeMismatch {
+            mismatches: vec![ArityMismatch { index: 1, id: HandlerId(2), expected: 1, got: 0 }],
+        }));
+        assert_eq!(dispatcher.manager().active_count(), 2);
+    }
+
+    #[test]
+    fn test_typed_dispatcher_catch_all() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut dispatcher = TypedDispatcher::<Event, i32>::new();
+        let output_clone = Arc::clone(&output);
+        let _h0 = dispatcher.on(
+            |event| match event {
+                Event::Move(x, y) => Some(CallbackParams::CallParams2(*x, *y)),
+                Event::Scroll(delta) => Some(CallbackParams::CallParams1(*delta)),
+            },
+            CallbackHandler::CatchAll(Box::new(move |arity, args| output_clone.lock().unwrap().push((arity, args)))),
+        ).unwrap();
+        let _h1 = dispatcher.on(|_event| None, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        dispatcher.emit(&Event::Move(1, 2)).unwrap();
+        dispatcher.emit(&Event::Scroll(3)).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(2, vec![1, 2]), (1, vec![3])]);
+    }
+}
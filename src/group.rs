@@ -0,0 +1,82 @@
+//! Broadcasting params to several managers at once.
+
+use crate::{CallbackError, CallbackManager, CallbackParams};
+
+/// A group of managers borrowed for dispatching the same params to each of them, without merging them.
+///
+/// # Examples
+///
+/// ```
+/// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, ManagerGroup};
+///
+/// let mut ui_manager = CallbackManager::<i32>::new();
+/// let mut log_manager = CallbackManager::<i32>::new();
+///
+/// let _ui_handler = ui_manager.add(CallbackHandler::Callback1(Box::new(|x| println!("ui: {x}")))).unwrap();
+/// let _log_handler = log_manager.add(CallbackHandler::Callback1(Box::new(|x| println!("log: {x}")))).unwrap();
+///
+/// let mut group = ManagerGroup::new(vec![&mut ui_manager, &mut log_manager]);
+///
+/// assert!(group.run_all(vec![CallbackParams::CallParams1(1)]).is_ok());
+/// ```
+pub struct ManagerGroup<'m, 'a, T: 'a> {
+    managers: Vec<&'m mut CallbackManager<'a, T>>,
+}
+
+impl<'m, 'a, T: 'a> ManagerGroup<'m, 'a, T> {
+    /// Creates a group of the given managers, dispatched in this order.
+    pub fn new(managers: Vec<&'m mut CallbackManager<'a, T>>) -> Self {
+        Self { managers }
+    }
+
+    /// Adds a manager, dispatched after the others.
+    pub fn push(&mut self, manager: &'m mut CallbackManager<'a, T>) {
+        self.managers.push(manager);
+    }
+}
+
+impl<'m, 'a, T: Copy + 'a> ManagerGroup<'m, 'a, T> {
+    /// Runs `run_all` with the same params on every manager of the group, in order.
+    ///
+    /// A failing manager doesn't stop the others from being dispatched. Each manager checks the params against
+    /// its own handlers, so managers expecting a different param count fail on their own. The errors are returned
+    /// together as `CallbackError::ManagersFailed`, each with the index of its manager in the group.
+    pub fn run_all(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        let errors = self.managers.iter_mut().enumerate().filter_map(
+            |(index, manager)| manager.run_all(params.clone()).err().map(|error| (index, error))
+        ).collect::<Vec<(usize, CallbackError)>>();
+        if !errors.is_empty() {
+            return Err(CallbackError::ManagersFailed { errors });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams, ManagerGroup};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_manager_group() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager1 = CallbackManager::<i32>::new();
+        let mut callback_manager2 = CallbackManager::<i32>::new();
+        let mut callback_manager3 = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager1.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push((1, x))))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager2.add(CallbackHandler::Callback1(Box::new(move |x| output2.lock().unwrap().push((2, x))))).unwrap();
+        let _h3 = callback_manager3.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h4 = callback_manager3.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        let mut group = ManagerGroup::new(vec![&mut callback_manager1, &mut callback_manager3]);
+        group.push(&mut callback_manager2);
+        let result = group.run_all(vec![CallbackParams::CallParams1(7)]);
+
+        assert_eq!(result, Err(CallbackError::ManagersFailed {
+            errors: vec![(1, CallbackError::ParamCountMismatch { expected: 2, got: 1 })],
+        }));
+        assert_eq!(*output.lock().unwrap(), vec![(1, 7), (2, 7)]);
+    }
+}
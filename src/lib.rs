@@ -1,29 +1,609 @@
 //! # Callback Manager
 //! 
 //! `callback_manager` is for registering and triggering callback functions taking arbitrary number of argument lists.
+//!
+//! ## Arguments of different types
+//!
+//! All the arguments of a manager share the type `T`. For handlers taking arguments of different types, use a
+//! tuple as `T` and single-argument handlers, whose closure destructures the tuple:
+//!
+//! ```
+//! use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+//!
+//! let mut cb_manager = CallbackManager::<(u32, &str)>::new();
+//!
+//! let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|(id, name)| println!("{id}: {name}")))).unwrap();
+//!
+//! assert!(cb_manager.run_all(vec![CallbackParams::CallParams1((1, "first"))]).is_ok());
+//! ```
+//!
+//! Tuples holding owned values like `String` aren't `Copy`; dispatch them with `run_all_consume` instead, or borrow
+//! them with a `RefCallbackManager`.
 
-use std::sync::{Arc, Weak, Mutex};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+mod async_handler;
+mod deferred;
+mod entries;
+mod event;
+mod group;
+mod prepared;
+mod ref_handler;
+mod registrar;
+mod returning;
+mod sync;
+mod typed;
+mod worker;
+
+#[cfg(feature = "async")]
+pub use async_handler::*;
+pub use deferred::*;
+pub use event::*;
+pub use group::*;
+pub use prepared::*;
+pub use ref_handler::*;
+pub use registrar::*;
+pub use returning::*;
+/// The mutex guarding each handler: `std::sync::Mutex`, or `parking_lot::Mutex` with the `parking_lot` feature.
+pub use sync::Mutex;
+pub use typed::*;
+pub use worker::*;
 
 
 /// Enumeration of callback handlers.
-pub enum CallbackHandler<'a, T: Copy + 'a> {
-    Callback0(Box<dyn FnMut() -> () + Send + 'a>),
-    Callback1(Box<dyn FnMut(T) -> () + Send + 'a>),
-    Callback2(Box<dyn FnMut(T, T) -> () + Send + 'a>),
-    Callback3(Box<dyn FnMut(T, T, T) -> () + Send + 'a>),
-    Callback4(Box<dyn FnMut(T, T, T, T) -> () + Send + 'a>),
-    Callback5(Box<dyn FnMut(T, T, T, T, T) -> () + Send + 'a>),
-    Callback6(Box<dyn FnMut(T, T, T, T, T, T) -> () + Send + 'a>),
-    Callback7(Box<dyn FnMut(T, T, T, T, T, T, T) -> () + Send + 'a>),
-    Callback8(Box<dyn FnMut(T, T, T, T, T, T, T, T) -> () + Send + 'a>),
-    Callback9(Box<dyn FnMut(T, T, T, T, T, T, T, T, T) -> () + Send + 'a>),
-    Callback10(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T) -> () + Send + 'a>,),
-    Callback11(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T, T) -> () + Send + 'a>,),
-    Callback12(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T, T, T) -> () + Send + 'a>,),
+#[allow(clippy::type_complexity)]
+pub enum CallbackHandler<'a, T: 'a> {
+    Callback0(Box<dyn FnMut() + Send + 'a>),
+    Callback1(Box<dyn FnMut(T) + Send + 'a>),
+    Callback2(Box<dyn FnMut(T, T) + Send + 'a>),
+    Callback3(Box<dyn FnMut(T, T, T) + Send + 'a>),
+    Callback4(Box<dyn FnMut(T, T, T, T) + Send + 'a>),
+    Callback5(Box<dyn FnMut(T, T, T, T, T) + Send + 'a>),
+    Callback6(Box<dyn FnMut(T, T, T, T, T, T) + Send + 'a>),
+    Callback7(Box<dyn FnMut(T, T, T, T, T, T, T) + Send + 'a>),
+    Callback8(Box<dyn FnMut(T, T, T, T, T, T, T, T) + Send + 'a>),
+    Callback9(Box<dyn FnMut(T, T, T, T, T, T, T, T, T) + Send + 'a>),
+    Callback10(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T) + Send + 'a>),
+    Callback11(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T, T) + Send + 'a>),
+    Callback12(Box<dyn FnMut(T, T, T, T, T, T, T, T, T, T, T, T) + Send + 'a>),
+    /// Invokes whichever of several closures matches the arity of its params, see [`MultiArityHandler`].
+    Multi(MultiArityHandler<'a, T>),
+    /// Receives every param passed to `run_all`, whatever its arity, as the arity and the arguments.
+    ///
+    /// A catch-all handler isn't paired with a param of its own: it is kept apart from the positional handlers,
+    /// so it isn't counted by `active_count` or listed by `active_ids` and similar queries. Catch-all handlers run
+    /// in registration order after all positional handlers ran successfully, each one receiving the params in
+    /// order. `run_all_unchecked`, `run_some` and `run_all_consume` don't invoke them.
+    CatchAll(Box<dyn FnMut(usize, Vec<T>) + Send + 'a>),
+    /// A handler that can be cloned with its state, see [`CloneableCallback`].
+    Cloneable(CloneableCallback<'a, T>),
+}
+
+impl<'a, T: 'a> CallbackHandler<'a, T> {
+    /// Returns a handler of the given arity that does nothing, or `None` if `arity` is greater than 12.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let handler = callback_manager::CallbackHandler::<i32>::noop(3).unwrap();
+    ///
+    /// assert_eq!(handler.arity(), 3);
+    /// ```
+    pub fn noop(arity: usize) -> Option<Self> {
+        Some(match arity {
+            0 => CallbackHandler::Callback0(Box::new(|| {})),
+            1 => CallbackHandler::Callback1(Box::new(|_| {})),
+            2 => CallbackHandler::Callback2(Box::new(|_, _| {})),
+            3 => CallbackHandler::Callback3(Box::new(|_, _, _| {})),
+            4 => CallbackHandler::Callback4(Box::new(|_, _, _, _| {})),
+            5 => CallbackHandler::Callback5(Box::new(|_, _, _, _, _| {})),
+            6 => CallbackHandler::Callback6(Box::new(|_, _, _, _, _, _| {})),
+            7 => CallbackHandler::Callback7(Box::new(|_, _, _, _, _, _, _| {})),
+            8 => CallbackHandler::Callback8(Box::new(|_, _, _, _, _, _, _, _| {})),
+            9 => CallbackHandler::Callback9(Box::new(|_, _, _, _, _, _, _, _, _| {})),
+            10 => CallbackHandler::Callback10(Box::new(|_, _, _, _, _, _, _, _, _, _| {})),
+            11 => CallbackHandler::Callback11(Box::new(|_, _, _, _, _, _, _, _, _, _, _| {})),
+            12 => CallbackHandler::Callback12(Box::new(|_, _, _, _, _, _, _, _, _, _, _, _| {})),
+            _ => return None,
+        })
+    }
+
+    /// Returns a handler that does nothing but accepts the same params as this one.
+    fn noop_like(&self) -> Self {
+        match self {
+            CallbackHandler::Multi(multi) => {
+                CallbackHandler::Multi(MultiArityHandler { arities: multi.arities.clone(), handler: Box::new(|_| {}) })
+            },
+            CallbackHandler::CatchAll(_) => CallbackHandler::CatchAll(Box::new(|_, _| {})),
+            CallbackHandler::Cloneable(cloneable) => {
+                CallbackHandler::Cloneable(CloneableCallback { arity: cloneable.arity, handler: Box::new(|_: &[T]| {}) })
+            },
+            handler => Self::noop(handler.arity()).expect("handler arity is at most 12"),
+        }
+    }
+
+    /// Returns the number of arguments the handler takes.
+    ///
+    /// For a `Multi` handler, this is the largest arity it accepts, or 0 if it accepts none. A `CatchAll` handler
+    /// accepts any arity and reports 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let handler = callback_manager::CallbackHandler::<i32>::Callback2(Box::new(|_x, _y| {}));
+    ///
+    /// assert_eq!(handler.arity(), 2);
+    /// ```
+    pub const fn arity(&self) -> usize {
+        match self {
+            CallbackHandler::Callback0(_) => 0,
+            CallbackHandler::Callback1(_) => 1,
+            CallbackHandler::Callback2(_) => 2,
+            CallbackHandler::Callback3(_) => 3,
+            CallbackHandler::Callback4(_) => 4,
+            CallbackHandler::Callback5(_) => 5,
+            CallbackHandler::Callback6(_) => 6,
+            CallbackHandler::Callback7(_) => 7,
+            CallbackHandler::Callback8(_) => 8,
+            CallbackHandler::Callback9(_) => 9,
+            CallbackHandler::Callback10(_) => 10,
+            CallbackHandler::Callback11(_) => 11,
+            CallbackHandler::Callback12(_) => 12,
+            CallbackHandler::Multi(multi) => {
+                // `Iterator::max` isn't const.
+                let arities = multi.arities.as_slice();
+                let mut max = 0;
+                let mut i = 0;
+                while i < arities.len() {
+                    if arities[i] > max {
+                        max = arities[i];
+                    }
+                    i += 1;
+                }
+                max
+            },
+            CallbackHandler::CatchAll(_) => 0,
+            CallbackHandler::Cloneable(cloneable) => cloneable.arity,
+        }
+    }
+
+    /// Returns whether `arity` returns `n`.
+    ///
+    /// A `Multi` handler only reports its largest accepted arity, see `arity`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let handler = callback_manager::CallbackHandler::<i32>::Callback2(Box::new(|_x, _y| {}));
+    ///
+    /// assert!(handler.is_arity(2));
+    /// ```
+    pub const fn is_arity(&self, n: usize) -> bool {
+        self.arity() == n
+    }
+
+    /// Returns whether the handler can be invoked with params of the given arity.
+    fn accepts(&self, arity: usize) -> bool {
+        match self {
+            CallbackHandler::Multi(multi) => multi.arities.contains(&arity),
+            CallbackHandler::CatchAll(_) => true,
+            handler => handler.arity() == arity,
+        }
+    }
+
+    /// Returns the largest arity up to `arity` that the handler accepts.
+    fn accepted_prefix(&self, arity: usize) -> Option<usize> {
+        match self {
+            CallbackHandler::Multi(multi) => multi.arities.iter().copied().filter(|&accepted| accepted <= arity).max(),
+            CallbackHandler::CatchAll(_) => Some(arity),
+            handler => Some(handler.arity()).filter(|&accepted| accepted <= arity),
+        }
+    }
+
+    /// Invokes the handler with the params, moving the arguments into the call.
+    ///
+    /// Returns `false` without invoking the handler if the arities differ.
+    fn call_owned(&mut self, params: CallbackParams<T>) -> bool {
+        match (self, params) {
+            (CallbackHandler::Callback0(handler), CallbackParams::CallParams0()) => handler(),
+            (CallbackHandler::Callback1(handler), CallbackParams::CallParams1(p1)) => handler(p1),
+            (CallbackHandler::Callback2(handler), CallbackParams::CallParams2(p1, p2)) => handler(p1, p2),
+            (CallbackHandler::Callback3(handler), CallbackParams::CallParams3(p1, p2, p3)) => handler(p1, p2, p3),
+            (CallbackHandler::Callback4(handler), CallbackParams::CallParams4(p1, p2, p3, p4)) => handler(p1, p2, p3, p4),
+            (CallbackHandler::Callback5(handler), CallbackParams::CallParams5(p1, p2, p3, p4, p5)) => handler(p1, p2, p3, p4, p5),
+            (CallbackHandler::Callback6(handler), CallbackParams::CallParams6(p1, p2, p3, p4, p5, p6)) => handler(p1, p2, p3, p4, p5, p6),
+            (CallbackHandler::Callback7(handler), CallbackParams::CallParams7(p1, p2, p3, p4, p5, p6, p7)) => handler(p1, p2, p3, p4, p5, p6, p7),
+            (CallbackHandler::Callback8(handler), CallbackParams::CallParams8(p1, p2, p3, p4, p5, p6, p7, p8)) => handler(p1, p2, p3, p4, p5, p6, p7, p8),
+            (CallbackHandler::Callback9(handler), CallbackParams::CallParams9(p1, p2, p3, p4, p5, p6, p7, p8, p9)) => handler(p1, p2, p3, p4, p5, p6, p7, p8, p9),
+            (CallbackHandler::Callback10(handler), CallbackParams::CallParams10(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10)) => handler(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10),
+            (CallbackHandler::Callback11(handler), CallbackParams::CallParams11(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11)) => handler(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11),
+            (CallbackHandler::Callback12(handler), CallbackParams::CallParams12(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12)) => handler(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12),
+            (CallbackHandler::Multi(multi), params) if multi.arities.contains(&params.arity()) => params.with_slice(&mut multi.handler),
+            (CallbackHandler::CatchAll(handler), params) => handler(params.arity(), params.into_vec()),
+            (CallbackHandler::Cloneable(cloneable), params) if cloneable.arity == params.arity() => params.with_slice(&mut cloneable.handler),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl<'a, T: Copy + 'a> CallbackHandler<'a, T> {
+    /// Invokes the handler with the params, returning `false` without invoking it if the arities differ.
+    fn call(&mut self, params: &CallbackParams<T>) -> bool {
+        self.call_owned(*params)
+    }
+
+    /// Binds `fixed` as the first argument, returning a handler taking one argument less.
+    ///
+    /// Returns `None` for a handler taking no arguments or a `CatchAll` handler. A `Multi` handler stops accepting
+    /// no arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::CallbackHandler;
+    ///
+    /// let handler = CallbackHandler::<i32>::Callback2(Box::new(|x, y| assert_eq!((x, y), (1, 2))));
+    /// let bound = handler.bind_first(1).unwrap();
+    ///
+    /// assert_eq!(bound.arity(), 1);
+    /// ```
+    pub fn bind_first(self, fixed: T) -> Option<Self> where T: Send {
+        if let CallbackHandler::CatchAll(_) = self {
+            return None;
+        }
+        let (arities, mut f) = self.into_slice_fn();
+        Self::from_arities_slice_fn(Self::bound_arities(arities)?, move |args| {
+            let mut all = [fixed; 12];
+            all[1..=args.len()].copy_from_slice(args);
+            f(&all[..=args.len()])
+        })
+    }
+
+    /// Binds `fixed` as the last argument, returning a handler taking one argument less.
+    ///
+    /// Returns `None` for a handler taking no arguments or a `CatchAll` handler. A `Multi` handler stops accepting
+    /// no arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::CallbackHandler;
+    ///
+    /// let handler = CallbackHandler::<i32>::Callback3(Box::new(|x, y, z| assert_eq!((x, y, z), (1, 2, 3))));
+    /// let bound = handler.bind_last(3).unwrap();
+    ///
+    /// assert_eq!(bound.arity(), 2);
+    /// ```
+    pub fn bind_last(self, fixed: T) -> Option<Self> where T: Send {
+        if let CallbackHandler::CatchAll(_) = self {
+            return None;
+        }
+        let (arities, mut f) = self.into_slice_fn();
+        Self::from_arities_slice_fn(Self::bound_arities(arities)?, move |args| {
+            let mut all = [fixed; 12];
+            all[..args.len()].copy_from_slice(args);
+            f(&all[..=args.len()])
+        })
+    }
+
+    /// Wraps the handler so that invocations beyond `max_per_sec` per second are skipped, counting each skipped
+    /// invocation in `dropped`.
+    fn rate_limited(self, max_per_sec: u32, dropped: Arc<AtomicUsize>) -> Self {
+        let mut bucket = TokenBucket::new(max_per_sec);
+        if let CallbackHandler::CatchAll(mut handler) = self {
+            return CallbackHandler::CatchAll(Box::new(move |arity, args| {
+                if bucket.try_take() {
+                    handler(arity, args);
+                } else {
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }));
+        }
+        let (arities, mut f) = self.into_slice_fn();
+        let limited = Self::from_arities_slice_fn(arities, move |args| {
+            if bucket.try_take() {
+                f(args);
+            } else {
+                dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        limited.expect("handler arity is at most 12")
+    }
+
+    /// Combines two handlers into one that invokes `a` and then `b` with the same arguments.
+    ///
+    /// The combined handler accepts the arities both handlers accept, and returns `None` if there are none. Two
+    /// `CatchAll` handlers combine into a `CatchAll` handler, but a `CatchAll` handler can't be combined with
+    /// another kind.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::chain2(
+    ///     CallbackHandler::Callback1(Box::new(|x| println!("first: {x}"))),
+    ///     CallbackHandler::Callback1(Box::new(|x| println!("second: {x}"))),
+    /// ).unwrap()).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_count(), 1);
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).is_ok());
+    /// ```
+    pub fn chain2(a: Self, b: Self) -> Option<Self> {
+        match (a, b) {
+            (CallbackHandler::CatchAll(mut a), CallbackHandler::CatchAll(mut b)) => {
+                Some(CallbackHandler::CatchAll(Box::new(move |arity, args: Vec<T>| {
+                    a(arity, args.clone());
+                    b(arity, args);
+                })))
+            },
+            (CallbackHandler::CatchAll(_), _) | (_, CallbackHandler::CatchAll(_)) => None,
+            (a, b) => {
+                let (a_arities, mut a) = a.into_slice_fn();
+                let (b_arities, mut b) = b.into_slice_fn();
+                let arities = a_arities.into_iter().filter(|arity| b_arities.contains(arity)).collect::<Vec<usize>>();
+                if arities.is_empty() {
+                    return None;
+                }
+                Self::from_arities_slice_fn(arities, move |args| {
+                    a(args);
+                    b(args);
+                })
+            },
+        }
+    }
+
+    /// Returns the arities left after binding one argument, or `None` if no arity takes an argument.
+    fn bound_arities(arities: Vec<usize>) -> Option<Vec<usize>> {
+        let arities = arities.into_iter().filter_map(|arity| arity.checked_sub(1)).collect::<Vec<usize>>();
+        (!arities.is_empty()).then_some(arities)
+    }
+
+    /// Adapts the handler to take arguments of another type, converting each argument with `f` before the call.
+    ///
+    /// This allows handlers written for different argument types to be registered with the same manager.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let handler = CallbackHandler::<f64>::Callback2(Box::new(|x, y| assert_eq!(x + y, 3.0)));
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let _handler = cb_manager.add(handler.map_arg(|x: i32| x as f64)).unwrap();
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).is_ok());
+    /// ```
+    pub fn map_arg<U: Copy + 'a>(self, f: impl Fn(U) -> T + Send + 'a) -> CallbackHandler<'a, U> {
+        if let CallbackHandler::CatchAll(mut handler) = self {
+            return CallbackHandler::CatchAll(Box::new(move |arity, args: Vec<U>| {
+                handler(arity, args.into_iter().map(&f).collect())
+            }));
+        }
+        let (arities, mut handler) = self.into_slice_fn();
+        let mapped = CallbackHandler::from_arities_slice_fn(arities, move |args: &[U]| match args.first() {
+            Some(first) => {
+                let mut converted = [f(*first); 12];
+                for (converted, arg) in converted[1..args.len()].iter_mut().zip(&args[1..]) {
+                    *converted = f(*arg);
+                }
+                handler(&converted[..args.len()])
+            },
+            None => handler(&[]),
+        });
+        mapped.expect("handler arity is at most 12")
+    }
+
+    /// Converts the handler into a closure taking its arguments as a slice, whose length must be one of the
+    /// returned arities.
+    fn into_slice_fn(self) -> (Vec<usize>, SliceFn<'a, T>) {
+        let arity = self.arity();
+        let f: SliceFn<'a, T> = match self {
+            CallbackHandler::Callback0(mut handler) => Box::new(move |_args: &[T]| handler()),
+            CallbackHandler::Callback1(mut handler) => Box::new(move |args: &[T]| handler(args[0])),
+            CallbackHandler::Callback2(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1])),
+            CallbackHandler::Callback3(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2])),
+            CallbackHandler::Callback4(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3])),
+            CallbackHandler::Callback5(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4])),
+            CallbackHandler::Callback6(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5])),
+            CallbackHandler::Callback7(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6])),
+            CallbackHandler::Callback8(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7])),
+            CallbackHandler::Callback9(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7], args[8])),
+            CallbackHandler::Callback10(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7], args[8], args[9])),
+            CallbackHandler::Callback11(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7], args[8], args[9], args[10])),
+            CallbackHandler::Callback12(mut handler) => Box::new(move |args: &[T]| handler(args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7], args[8], args[9], args[10], args[11])),
+            CallbackHandler::Multi(multi) => return (multi.arities, multi.handler),
+            CallbackHandler::CatchAll(mut handler) => {
+                return ((0..=12).collect(), Box::new(move |args: &[T]| handler(args.len(), args.to_vec())));
+            },
+            CallbackHandler::Cloneable(mut cloneable) => Box::new(move |args: &[T]| (cloneable.handler)(args)),
+        };
+        (vec![arity], f)
+    }
+
+    /// Builds a handler accepting the given arities from a closure taking its arguments as a slice.
+    ///
+    /// A single arity gives a plain handler of that arity, anything else a `Multi` handler.
+    fn from_arities_slice_fn(arities: Vec<usize>, f: impl FnMut(&[T]) + Send + 'a) -> Option<Self> {
+        match arities[..] {
+            [arity] => Self::from_slice_fn(arity, f),
+            _ if arities.iter().all(|&arity| arity <= 12) => {
+                Some(CallbackHandler::Multi(MultiArityHandler { arities, handler: Box::new(f) }))
+            },
+            _ => None,
+        }
+    }
+
+    /// Builds a handler of the given arity from a closure taking its arguments as a slice.
+    fn from_slice_fn(arity: usize, mut f: impl FnMut(&[T]) + Send + 'a) -> Option<Self> {
+        Some(match arity {
+            0 => CallbackHandler::Callback0(Box::new(move || f(&[]))),
+            1 => CallbackHandler::Callback1(Box::new(move |p1| f(&[p1]))),
+            2 => CallbackHandler::Callback2(Box::new(move |p1, p2| f(&[p1, p2]))),
+            3 => CallbackHandler::Callback3(Box::new(move |p1, p2, p3| f(&[p1, p2, p3]))),
+            4 => CallbackHandler::Callback4(Box::new(move |p1, p2, p3, p4| f(&[p1, p2, p3, p4]))),
+            5 => CallbackHandler::Callback5(Box::new(move |p1, p2, p3, p4, p5| f(&[p1, p2, p3, p4, p5]))),
+            6 => CallbackHandler::Callback6(Box::new(move |p1, p2, p3, p4, p5, p6| f(&[p1, p2, p3, p4, p5, p6]))),
+            7 => CallbackHandler::Callback7(Box::new(move |p1, p2, p3, p4, p5, p6, p7| f(&[p1, p2, p3, p4, p5, p6, p7]))),
+            8 => CallbackHandler::Callback8(Box::new(move |p1, p2, p3, p4, p5, p6, p7, p8| f(&[p1, p2, p3, p4, p5, p6, p7, p8]))),
+            9 => CallbackHandler::Callback9(Box::new(move |p1, p2, p3, p4, p5, p6, p7, p8, p9| f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9]))),
+            10 => CallbackHandler::Callback10(Box::new(move |p1, p2, p3, p4, p5, p6, p7, p8, p9, p10| f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10]))),
+            11 => CallbackHandler::Callback11(Box::new(move |p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11| f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11]))),
+            12 => CallbackHandler::Callback12(Box::new(move |p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12| f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12]))),
+            _ => return None,
+        })
+    }
+}
+
+type SliceFn<'a, T> = Box<dyn FnMut(&[T]) + Send + 'a>;
+
+/// A token bucket holding up to one second's worth of tokens, refilled continuously.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(max_per_sec: u32) -> Self {
+        Self { rate: max_per_sec.into(), tokens: max_per_sec.into(), refilled: Instant::now() }
+    }
+
+    /// Takes a token if one is available.
+    fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.refilled).as_secs_f64() * self.rate).min(self.rate);
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// A handler bundling closures of several arities, registered as a single `CallbackHandler::Multi`.
+///
+/// Each dispatch invokes the closure matching the arity of the param paired with the handler. It still counts as
+/// one handler, so `run_all` expects exactly one param for it, of any arity it accepts. A param of another arity is
+/// a mismatch, reported against the largest accepted arity. Methods grouping handlers by arity, like
+/// `run_all_bucketed`, use that largest arity too.
+pub struct MultiArityHandler<'a, T: 'a> {
+    arities: Vec<usize>,
+    handler: SliceFn<'a, T>,
+}
+
+impl<'a, T: 'a> MultiArityHandler<'a, T> {
+    /// Returns the arities the handler accepts.
+    pub fn arities(&self) -> &[usize] {
+        &self.arities
+    }
+}
+
+impl<'a, T: Copy + 'a> MultiArityHandler<'a, T> {
+    /// Bundles `handlers` into one handler. When several accept the same arity, the first of them is invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, MultiArityHandler};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Multi(MultiArityHandler::new(vec![
+    ///     CallbackHandler::Callback1(Box::new(|x| println!("one value: {x}"))),
+    ///     CallbackHandler::Callback2(Box::new(|x, y| println!("two values: {x}, {y}"))),
+    /// ]))).unwrap();
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).is_ok());
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).is_ok());
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams0()]).is_err());
+    /// ```
+    pub fn new(handlers: Vec<CallbackHandler<'a, T>>) -> Self {
+        let mut arities = vec![];
+        let mut parts = vec![];
+        for handler in handlers {
+            let (handler_arities, f) = handler.into_slice_fn();
+            let handler_arities = handler_arities.into_iter().filter(|arity| !arities.contains(arity)).collect::<Vec<usize>>();
+            arities.extend_from_slice(&handler_arities);
+            parts.push((handler_arities, f));
+        }
+        let handler = move |args: &[T]| {
+            if let Some((_, f)) = parts.iter_mut().find(|(arities, _)| arities.contains(&args.len())) {
+                f(args);
+            }
+        };
+        MultiArityHandler { arities, handler: Box::new(handler) }
+    }
+}
+
+/// A closure taking its arguments as a slice that can be cloned behind a `Box`.
+trait CloneSliceFn<'a, T>: FnMut(&[T]) + Send {
+    fn clone_box(&self) -> Box<dyn CloneSliceFn<'a, T> + 'a>;
+}
+
+impl<'a, T, F: FnMut(&[T]) + Clone + Send + 'a> CloneSliceFn<'a, T> for F {
+    fn clone_box(&self) -> Box<dyn CloneSliceFn<'a, T> + 'a> {
+        Box::new(self.clone())
+    }
+}
+
+/// A handler whose closure, along with its state, can be cloned, registered as `CallbackHandler::Cloneable`.
+///
+/// Only cloneable handlers can take part in `CallbackManager::run_all_scoped`. Cloning clones the closure's
+/// captured state, so state owned by the closure is rolled back, while state it shares through an `Arc` or a
+/// reference is not.
+pub struct CloneableCallback<'a, T: 'a> {
+    arity: usize,
+    handler: Box<dyn CloneSliceFn<'a, T> + 'a>,
+}
+
+impl<'a, T: 'a> CloneableCallback<'a, T> {
+    /// Wraps a `Clone` closure taking its arguments as a slice of exactly `arity` elements.
+    ///
+    /// Fails with `CallbackError::InvalidArity` if `arity` is greater than 12.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CloneableCallback};
+    ///
+    /// let mut count = 0;
+    /// let handler = CloneableCallback::<i32>::new(2, move |args| {
+    ///     count += 1;
+    ///     println!("{count}: {}", args[0] + args[1]);
+    /// }).unwrap();
+    ///
+    /// assert_eq!(CallbackHandler::Cloneable(handler).arity(), 2);
+    /// ```
+    pub fn new(arity: usize, handler: impl FnMut(&[T]) + Clone + Send + 'a) -> Result<Self, CallbackError> {
+        if arity > 12 {
+            return Err(CallbackError::InvalidArity { arity });
+        }
+        Ok(CloneableCallback { arity, handler: Box::new(handler) })
+    }
+}
+
+impl<'a, T: 'a> Clone for CloneableCallback<'a, T> {
+    fn clone(&self) -> Self {
+        CloneableCallback { arity: self.arity, handler: self.handler.clone_box() }
+    }
 }
 
 /// Enumeration of parameter lists for each callback handler types.
-pub enum CallbackParams<T: Copy> {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallbackParams<T> {
     CallParams0(),
     CallParams1(T),
     CallParams2(T, T),
@@ -39,364 +619,3176 @@ pub enum CallbackParams<T: Copy> {
     CallParams12(T, T, T, T, T, T, T, T, T, T, T, T),
 }
 
-/// A callback manager struct which holds and triggers collback handlers.
-pub struct CallbackManager<'a, T: Copy + 'a> {
-    pub handlers: Vec<Weak<Mutex<CallbackHandler<'a, T>>>>
-}
-
-impl<'a, T: Copy + 'a> CallbackManager<'a, T> {
-    /// Creates a new `CallbackManager` instance.
-    /// 
+impl<T> CallbackParams<T> {
+    /// Returns the number of arguments in the parameter list.
+    ///
     /// # Examples
+    ///
     /// ```
-    /// let _cb_manager = callback_manager::CallbackManager::<i32>::new();
-    /// 
-    /// assert_eq!(_cb_manager.handlers.len(), 0);
+    /// let params = callback_manager::CallbackParams::CallParams3(1, 2, 3);
+    ///
+    /// assert_eq!(params.arity(), 3);
+    ///
+    /// const ARITY: usize = callback_manager::CallbackParams::CallParams2(1, 2).arity();
+    /// assert_eq!(ARITY, 2);
     /// ```
-    pub fn new() -> Self {
-        Self {
-            handlers: vec![]
+    pub const fn arity(&self) -> usize {
+        match self {
+            CallbackParams::CallParams0() => 0,
+            CallbackParams::CallParams1(_) => 1,
+            CallbackParams::CallParams2(..) => 2,
+            CallbackParams::CallParams3(..) => 3,
+            CallbackParams::CallParams4(..) => 4,
+            CallbackParams::CallParams5(..) => 5,
+            CallbackParams::CallParams6(..) => 6,
+            CallbackParams::CallParams7(..) => 7,
+            CallbackParams::CallParams8(..) => 8,
+            CallbackParams::CallParams9(..) => 9,
+            CallbackParams::CallParams10(..) => 10,
+            CallbackParams::CallParams11(..) => 11,
+            CallbackParams::CallParams12(..) => 12,
         }
     }
 
-    /// Adds a new callback handler.
-    /// 
+    /// Returns whether the parameter list holds `n` arguments.
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
-    /// fn print(x: i32) {
-    ///     println!("number is {x}");
-    /// }
-    /// 
-    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
-    /// 
-    /// cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(print)));
-    /// 
-    /// assert_eq!(cb_manager.handlers.len(), 1);
+    /// let params = callback_manager::CallbackParams::CallParams1(1);
+    ///
+    /// assert!(params.is_arity(1));
     /// ```
-    pub fn add(&mut self, handler: CallbackHandler<'a, T>) -> Arc<Mutex<CallbackHandler<'a, T>>> {
-        let strong_handler = Arc::new(Mutex::new(handler));
-        self.handlers.push(Arc::downgrade(&strong_handler));
-        Arc::clone(&strong_handler)
+    pub const fn is_arity(&self, n: usize) -> bool {
+        self.arity() == n
     }
 
-    /// Returns active handler counts.
-    /// 
+    /// Builds the params variant matching the length of `args`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is greater than 12. Use `TryFrom` to handle that case instead.
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
-    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
-    /// 
-    /// let _handler = cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(|x: i32| {println!("number is {x}")})));
-    /// 
-    /// assert_eq!(cb_manager.active_count(), 1);
+    /// use callback_manager::CallbackParams;
+    ///
+    /// assert_eq!(CallbackParams::from_array([1, 2, 3]), CallbackParams::CallParams3(1, 2, 3));
     /// ```
-    pub fn active_count(&self) -> usize {
-        let mut r = 0;
-        for weak_handler in self.handlers.iter() {
-            if let Some(_) = Weak::upgrade(weak_handler) {
-                r += 1;
-            }
+    pub fn from_array<const N: usize>(args: [T; N]) -> Self {
+        match Self::try_from(Vec::from(args)) {
+            Ok(params) => params,
+            Err(error) => panic!("{error}"),
         }
-        r
     }
 
-    fn drop_inactive(&mut self) {
-        self.handlers = self.handlers.clone().into_iter().filter(
-            |x| if let Some(_) = Weak::upgrade(x) {
-                true
-            } else {
-                false
-            }
-        ).collect::<Vec<Weak<Mutex<CallbackHandler<'a, T>>>>>();
-    }
-
-    fn try_match_params(&self, params: &Vec<CallbackParams<T>>) -> Result<(), String> {
-        if params.len() != self.active_count() {
-            return Err(String::from("mismatched param counts to active handlers"));
-        }
-
-        let mismatching_results = self.handlers.iter().enumerate().into_iter().filter(
-            |item| 
-                if let Some(mutex_handler) = Weak::upgrade(item.1) {
-                    if let Ok(guard_handler) = mutex_handler.lock() {
-                        match *guard_handler {
-                            CallbackHandler::Callback0(_) => {
-                                if let Some(CallbackParams::CallParams0()) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback1(_) => {
-                                if let Some(CallbackParams::CallParams1(_)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback2(_) => {
-                                if let Some(CallbackParams::CallParams2(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback3(_) => {
-                                if let Some(CallbackParams::CallParams3(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback4(_) => {
-                                if let Some(CallbackParams::CallParams4(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback5(_) => {
-                                if let Some(CallbackParams::CallParams5(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback6(_) => {
-                                if let Some(CallbackParams::CallParams6(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback7(_) => {
-                                if let Some(CallbackParams::CallParams7(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback8(_) => {
-                                if let Some(CallbackParams::CallParams8(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback9(_) => {
-                                if let Some(CallbackParams::CallParams9(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback10(_) => {
-                                if let Some(CallbackParams::CallParams10(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback11(_) => {
-                                if let Some(CallbackParams::CallParams11(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                            CallbackHandler::Callback12(_) => {
-                                if let Some(CallbackParams::CallParams12(..)) = params.get(item.0) {
-                                    false
-                                } else {
-                                    true
-                                }
-                            },
-                        }
-                    } else {
-                        true
-                    }
-                } else {
-                    true
-                }
-        ).collect::<Vec<(usize, &Weak<Mutex<CallbackHandler<T>>>)>>();
+    /// Builds the params variant of the given arity from `args`, checking them at runtime.
+    ///
+    /// This is the dynamic counterpart to `from_array`, for arguments whose count is only known at runtime. Fails
+    /// with `CallbackError::InvalidArity` if `arity` is greater than 12, or with `CallbackError::ArgCountMismatch`
+    /// if `args` doesn't hold exactly `arity` arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackError, CallbackParams};
+    ///
+    /// assert_eq!(CallbackParams::try_new(2, &[1, 2]), Ok(CallbackParams::CallParams2(1, 2)));
+    /// assert_eq!(CallbackParams::try_new(3, &[1, 2]), Err(CallbackError::ArgCountMismatch { expected: 3, got: 2 }));
+    /// ```
+    pub fn try_new(arity: usize, args: &[T]) -> Result<Self, CallbackError> where T: Clone {
+        if arity > 12 {
+            return Err(CallbackError::InvalidArity { arity });
+        }
+        if args.len() != arity {
+            return Err(CallbackError::ArgCountMismatch { expected: arity, got: args.len() });
+        }
+        Self::try_from(args)
+    }
 
-        if mismatching_results.len() > 0 {
-            return Err(format!("mismatching params for {} handlers", mismatching_results.len()));
+    /// Moves the arguments into a `Vec`.
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            CallbackParams::CallParams0() => vec![],
+            CallbackParams::CallParams1(p1) => vec![p1],
+            CallbackParams::CallParams2(p1, p2) => vec![p1, p2],
+            CallbackParams::CallParams3(p1, p2, p3) => vec![p1, p2, p3],
+            CallbackParams::CallParams4(p1, p2, p3, p4) => vec![p1, p2, p3, p4],
+            CallbackParams::CallParams5(p1, p2, p3, p4, p5) => vec![p1, p2, p3, p4, p5],
+            CallbackParams::CallParams6(p1, p2, p3, p4, p5, p6) => vec![p1, p2, p3, p4, p5, p6],
+            CallbackParams::CallParams7(p1, p2, p3, p4, p5, p6, p7) => vec![p1, p2, p3, p4, p5, p6, p7],
+            CallbackParams::CallParams8(p1, p2, p3, p4, p5, p6, p7, p8) => vec![p1, p2, p3, p4, p5, p6, p7, p8],
+            CallbackParams::CallParams9(p1, p2, p3, p4, p5, p6, p7, p8, p9) => vec![p1, p2, p3, p4, p5, p6, p7, p8, p9],
+            CallbackParams::CallParams10(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10) => vec![p1, p2, p3, p4, p5, p6, p7, p8, p9, p10],
+            CallbackParams::CallParams11(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11) => vec![p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11],
+            CallbackParams::CallParams12(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12) => vec![p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12],
         }
+    }
 
-        Ok(())
+    /// Passes the arguments to `f` as a slice.
+    fn with_slice<R>(self, f: impl FnOnce(&[T]) -> R) -> R {
+        match self {
+            CallbackParams::CallParams0() => f(&[]),
+            CallbackParams::CallParams1(p1) => f(&[p1]),
+            CallbackParams::CallParams2(p1, p2) => f(&[p1, p2]),
+            CallbackParams::CallParams3(p1, p2, p3) => f(&[p1, p2, p3]),
+            CallbackParams::CallParams4(p1, p2, p3, p4) => f(&[p1, p2, p3, p4]),
+            CallbackParams::CallParams5(p1, p2, p3, p4, p5) => f(&[p1, p2, p3, p4, p5]),
+            CallbackParams::CallParams6(p1, p2, p3, p4, p5, p6) => f(&[p1, p2, p3, p4, p5, p6]),
+            CallbackParams::CallParams7(p1, p2, p3, p4, p5, p6, p7) => f(&[p1, p2, p3, p4, p5, p6, p7]),
+            CallbackParams::CallParams8(p1, p2, p3, p4, p5, p6, p7, p8) => f(&[p1, p2, p3, p4, p5, p6, p7, p8]),
+            CallbackParams::CallParams9(p1, p2, p3, p4, p5, p6, p7, p8, p9) => f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9]),
+            CallbackParams::CallParams10(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10) => f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10]),
+            CallbackParams::CallParams11(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11) => f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11]),
+            CallbackParams::CallParams12(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12) => f(&[p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12]),
+        }
     }
+}
 
-    /// Runs all active callback handlers with specific parameter lists.
-    /// 
+impl<T> TryFrom<Vec<T>> for CallbackParams<T> {
+    type Error = CallbackError;
+
+    /// Builds the params variant matching the length of `args`, failing with `CallbackError::InvalidArity` if it
+    /// is greater than 12.
+    fn try_from(args: Vec<T>) -> Result<Self, CallbackError> {
+        let arity = args.len();
+        let mut args = args.into_iter();
+        let mut p = || args.next().expect("arity checked against the argument count");
+        Ok(match arity {
+            0 => CallbackParams::CallParams0(),
+            1 => CallbackParams::CallParams1(p()),
+            2 => CallbackParams::CallParams2(p(), p()),
+            3 => CallbackParams::CallParams3(p(), p(), p()),
+            4 => CallbackParams::CallParams4(p(), p(), p(), p()),
+            5 => CallbackParams::CallParams5(p(), p(), p(), p(), p()),
+            6 => CallbackParams::CallParams6(p(), p(), p(), p(), p(), p()),
+            7 => CallbackParams::CallParams7(p(), p(), p(), p(), p(), p(), p()),
+            8 => CallbackParams::CallParams8(p(), p(), p(), p(), p(), p(), p(), p()),
+            9 => CallbackParams::CallParams9(p(), p(), p(), p(), p(), p(), p(), p(), p()),
+            10 => CallbackParams::CallParams10(p(), p(), p(), p(), p(), p(), p(), p(), p(), p()),
+            11 => CallbackParams::CallParams11(p(), p(), p(), p(), p(), p(), p(), p(), p(), p(), p()),
+            12 => CallbackParams::CallParams12(p(), p(), p(), p(), p(), p(), p(), p(), p(), p(), p(), p()),
+            _ => return Err(CallbackError::InvalidArity { arity }),
+        })
+    }
+}
+
+impl<T: Clone> TryFrom<&[T]> for CallbackParams<T> {
+    type Error = CallbackError;
+
+    /// Builds the params variant matching the length of `args`, failing with `CallbackError::InvalidArity` if it
+    /// is greater than 12.
+    fn try_from(args: &[T]) -> Result<Self, CallbackError> {
+        Self::try_from(args.to_vec())
+    }
+}
+
+impl<T: Copy> CallbackParams<T> {
+    /// Returns the argument at position `n`, or `None` if the parameter list holds `n` arguments or fewer.
+    ///
     /// # Examples
-    /// 
+    ///
     /// ```
-    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
-    /// 
-    /// let mut sum = 0;
-    /// let sum_pointer = &sum as *const i32;
-    /// 
-    /// let _handler0 = cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(|x: i32| {sum += x;})));
-    /// 
-    /// cb_manager.run_all(vec![
-    ///     callback_manager::CallbackParams::CallParams1(100),
-    /// ]);
-    /// 
-    /// unsafe {assert_eq!(*sum_pointer, 100);}
+    /// use callback_manager::CallbackParams;
+    ///
+    /// let params = CallbackParams::CallParams3(1, 2, 3);
+    ///
+    /// assert_eq!(params.get_arg(1), Some(2));
+    /// assert_eq!(params.get_arg(3), None);
     /// ```
-    pub fn run_all(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), String> {
-        self.drop_inactive();
+    pub fn get_arg(&self, n: usize) -> Option<T> {
+        self.with_slice(|args| args.get(n).copied())
+    }
 
-        self.try_match_params(&params)?;
-
-        for (index, weak_handler) in self.handlers.iter().enumerate().into_iter() {
-            if let Some(mutex_handler) = Weak::upgrade(weak_handler) {
-                if let Ok(mut guard_handler) = mutex_handler.lock() {
-                    match &mut *guard_handler {
-                        CallbackHandler::Callback0(handler) => {
-                            if let Some(CallbackParams::CallParams0()) = params.get(index) {
-                                (*handler)();
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback1(handler) => {
-                            if let Some(CallbackParams::CallParams1(p1)) = params.get(index) {
-                                (*handler)(*p1);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback2(handler) => {
-                            if let Some(CallbackParams::CallParams2(p1, p2)) = params.get(index) {
-                                (*handler)(*p1, *p2);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback3(handler) => {
-                            if let Some(CallbackParams::CallParams3(p1, p2, p3)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback4(handler) => {
-                            if let Some(CallbackParams::CallParams4(p1, p2, p3, p4)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback5(handler) => {
-                            if let Some(CallbackParams::CallParams5(p1, p2, p3, p4, p5)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback6(handler) => {
-                            if let Some(CallbackParams::CallParams6(p1, p2, p3, p4, p5, p6)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback7(handler) => {
-                            if let Some(CallbackParams::CallParams7(p1, p2, p3, p4, p5, p6, p7)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback8(handler) => {
-                            if let Some(CallbackParams::CallParams8(p1, p2, p3, p4, p5, p6, p7, p8)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7, *p8);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback9(handler) => {
-                            if let Some(CallbackParams::CallParams9(p1, p2, p3, p4, p5, p6, p7, p8, p9)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7, *p8, *p9);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback10(handler) => {
-                            if let Some(CallbackParams::CallParams10(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7, *p8, *p9, *p10);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback11(handler) => {
-                            if let Some(CallbackParams::CallParams11(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7, *p8, *p9, *p10, *p11);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                        CallbackHandler::Callback12(handler) => {
-                            if let Some(CallbackParams::CallParams12(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12)) = params.get(index) {
-                                (*handler)(*p1, *p2, *p3, *p4, *p5, *p6, *p7, *p8, *p9, *p10, *p11, *p12);
-                            } else {
-                                return Err(String::from("unexpected mismatching param"));
-                            }
-                        },
-                    }
-                } else {
-                    return Err(String::from("retreiving mutex guard of handler failure"));
+    /// Returns the number of arguments in the parameter list, like `arity`.
+    ///
+    /// Reads better next to `get_arg`, as the valid positions are `0..arg_count()`.
+    pub const fn arg_count(&self) -> usize {
+        self.arity()
+    }
+
+    /// Applies `f` to every argument, keeping the arity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::CallbackParams;
+    ///
+    /// let params = CallbackParams::CallParams2(1, 2);
+    ///
+    /// assert_eq!(params.map(|x| x * 10), CallbackParams::CallParams2(10, 20));
+    /// ```
+    pub fn map<U: Copy>(&self, mut f: impl FnMut(T) -> U) -> CallbackParams<U> {
+        match self {
+            CallbackParams::CallParams0() => CallbackParams::CallParams0(),
+            CallbackParams::CallParams1(p1) => CallbackParams::CallParams1(f(*p1)),
+            CallbackParams::CallParams2(p1, p2) => CallbackParams::CallParams2(f(*p1), f(*p2)),
+            CallbackParams::CallParams3(p1, p2, p3) => CallbackParams::CallParams3(f(*p1), f(*p2), f(*p3)),
+            CallbackParams::CallParams4(p1, p2, p3, p4) => CallbackParams::CallParams4(f(*p1), f(*p2), f(*p3), f(*p4)),
+            CallbackParams::CallParams5(p1, p2, p3, p4, p5) => CallbackParams::CallParams5(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5)),
+            CallbackParams::CallParams6(p1, p2, p3, p4, p5, p6) => CallbackParams::CallParams6(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6)),
+            CallbackParams::CallParams7(p1, p2, p3, p4, p5, p6, p7) => CallbackParams::CallParams7(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7)),
+            CallbackParams::CallParams8(p1, p2, p3, p4, p5, p6, p7, p8) => CallbackParams::CallParams8(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7), f(*p8)),
+            CallbackParams::CallParams9(p1, p2, p3, p4, p5, p6, p7, p8, p9) => CallbackParams::CallParams9(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7), f(*p8), f(*p9)),
+            CallbackParams::CallParams10(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10) => CallbackParams::CallParams10(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7), f(*p8), f(*p9), f(*p10)),
+            CallbackParams::CallParams11(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11) => CallbackParams::CallParams11(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7), f(*p8), f(*p9), f(*p10), f(*p11)),
+            CallbackParams::CallParams12(p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12) => CallbackParams::CallParams12(f(*p1), f(*p2), f(*p3), f(*p4), f(*p5), f(*p6), f(*p7), f(*p8), f(*p9), f(*p10), f(*p11), f(*p12)),
+        }
+    }
+}
+
+/// Identifier assigned to a handler when it is registered.
+///
+/// Ids are unique within a manager and never reused. They order by registration, so sorting ids gives a
+/// deterministic order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HandlerId(pub u64);
+
+/// Read-only description of a live handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandlerInfo {
+    pub id: HandlerId,
+    pub arity: usize,
+}
+
+/// A handler whose paired params have a different arity than the handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArityMismatch {
+    pub index: usize,
+    pub id: HandlerId,
+    pub expected: usize,
+    pub got: usize,
+}
+
+impl fmt::Display for ArityMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "handler {} at index {} expects {} params, got {}", self.id.0, self.index, self.expected, self.got)
+    }
+}
+
+/// Errors returned by callback manager operations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallbackError {
+    /// The number of params differs from the number of active handlers.
+    ParamCountMismatch { expected: usize, got: usize },
+    /// Some params don't match the arity of the handlers they are paired with.
+    ParamTypeMismatch { mismatches: Vec<ArityMismatch> },
+    /// A handler was dropped while being dispatched.
+    HandlerDropped,
+    /// A handler's mutex was poisoned by a panicking callback.
+    LockPoisoned,
+    /// The params bucket for an arity doesn't hold one param per live handler of that arity.
+    BucketSizeMismatch { arity: usize, expected: usize, got: usize },
+    /// No live handler is registered under these ids.
+    UnknownHandlers { ids: Vec<HandlerId> },
+    /// The live handlers differ from the expected snapshot of ids and arities.
+    SnapshotMismatch { expected: Vec<(HandlerId, usize)>, actual: Vec<(HandlerId, usize)> },
+    /// The arities of a batch of handlers differ from the expected template.
+    TemplateMismatch { expected: Vec<usize>, actual: Vec<usize> },
+    /// No handler type takes this many params; at most 12 are supported.
+    InvalidArity { arity: usize },
+    /// The manager was frozen with `freeze`, so no more handlers can be registered.
+    Frozen,
+    /// These handlers aren't `CallbackHandler::Cloneable`, so they can't be run on clones.
+    NotCloneable { ids: Vec<HandlerId> },
+    /// The number of arguments differs from the arity of the params being built.
+    ArgCountMismatch { expected: usize, got: usize },
+    /// The handler has no remembered params, as it wasn't invoked since params started being remembered.
+    NotInvoked { id: HandlerId },
+    /// Some managers of a `ManagerGroup` failed, listed with their index in the group.
+    ManagersFailed { errors: Vec<(usize, CallbackError)> },
+    /// Handlers kept queueing deferred dispatches past the dispatcher's limit.
+    DeferredLimitExceeded { limit: usize },
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackError::ParamCountMismatch { expected, got } => {
+                write!(f, "mismatched param counts to active handlers: expected {expected}, got {got}")
+            },
+            CallbackError::ParamTypeMismatch { mismatches } => {
+                write!(f, "mismatching params for {} handlers", mismatches.len())?;
+                for (i, mismatch) in mismatches.iter().enumerate() {
+                    write!(f, "{}{mismatch}", if i == 0 { ": " } else { "; " })?;
                 }
-            } else {
-                return Err(String::from("unexpected dropped handler"));
-            }
+                Ok(())
+            },
+            CallbackError::HandlerDropped => write!(f, "unexpected dropped handler"),
+            CallbackError::LockPoisoned => write!(f, "retreiving mutex guard of handler failure"),
+            CallbackError::BucketSizeMismatch { arity, expected, got } => {
+                write!(f, "mismatched param counts to active handlers of arity {arity}: expected {expected}, got {got}")
+            },
+            CallbackError::UnknownHandlers { ids } => {
+                let ids = ids.iter().map(|id| id.0.to_string()).collect::<Vec<String>>();
+                write!(f, "unknown or dropped handlers: {}", ids.join(", "))
+            },
+            CallbackError::SnapshotMismatch { expected, actual } => {
+                write!(f, "handlers don't match snapshot: expected {expected:?}, got {actual:?}")
+            },
+            CallbackError::TemplateMismatch { expected, actual } => {
+                write!(f, "handler arities don't match template: expected {expected:?}, got {actual:?}")
+            },
+            CallbackError::InvalidArity { arity } => write!(f, "unsupported arity {arity}, at most 12 params are supported"),
+            CallbackError::Frozen => write!(f, "manager is frozen, no more handlers can be registered"),
+            CallbackError::NotCloneable { ids } => {
+                let ids = ids.iter().map(|id| id.0.to_string()).collect::<Vec<String>>();
+                write!(f, "handlers can't be cloned: {}", ids.join(", "))
+            },
+            CallbackError::ArgCountMismatch { expected, got } => write!(f, "expected {expected} arguments, got {got}"),
+            CallbackError::NotInvoked { id } => write!(f, "handler {} has no remembered params", id.0),
+            CallbackError::ManagersFailed { errors } => {
+                write!(f, "{} managers failed", errors.len())?;
+                for (i, (index, error)) in errors.iter().enumerate() {
+                    write!(f, "{}manager {index}: {error}", if i == 0 { ": " } else { "; " })?;
+                }
+                Ok(())
+            },
+            CallbackError::DeferredLimitExceeded { limit } => write!(f, "more than {limit} deferred dispatches were queued"),
         }
+    }
+}
 
-        Ok(())
+impl std::error::Error for CallbackError {}
+
+/// A completed dispatch, sent to the observer set with `CallbackManager::set_observer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DispatchEvent {
+    /// When the dispatch completed.
+    pub timestamp: Instant,
+    /// The ids of the handlers this manager invoked, in order. Chained managers report to their own observers.
+    pub ids_invoked: Vec<HandlerId>,
+    /// The error the dispatch failed with, if any.
+    pub error: Option<CallbackError>,
+}
+
+/// Handler counts by state, as returned by `CallbackManager::len_by_state`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ManagerStats {
+    /// Live handlers, including poisoned ones.
+    pub active: usize,
+    /// Entries whose handler was dropped but which haven't been cleaned up yet.
+    pub dead: usize,
+    /// Live handlers whose mutex was poisoned by a panicking callback.
+    pub poisoned: usize,
+}
+
+impl fmt::Display for ManagerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} active ({} poisoned), {} dead", self.active, self.poisoned, self.dead)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Description of a registered handler, as part of a `ManagerDescription`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HandlerDescription {
+    pub id: HandlerId,
+    pub arity: usize,
+    /// Whether this is a `CatchAll` handler, which isn't paired with a param.
+    pub catch_all: bool,
+    /// Whether a panicking callback poisoned the handler's mutex.
+    pub poisoned: bool,
+    /// The number of recent invocations kept by `track_invocations`, or `None` if it isn't enabled.
+    pub recent_invocations: Option<usize>,
+}
 
-    static mut OUTPUT: Vec<String> = vec![];
+/// Description of a manager's live handlers, as returned by `CallbackManager::describe`.
+///
+/// Serializable with the `serde` feature, for instance to export the handler topology to monitoring tools. The
+/// callbacks themselves aren't described.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ManagerDescription {
+    /// The live handlers in dispatch order, positional handlers first.
+    pub handlers: Vec<HandlerDescription>,
+    pub frozen: bool,
+}
 
-    fn func0() {
-        unsafe { OUTPUT.push("calling func0".to_string()); }
+/// A recorded `run_all` call: the params it was given and the ids of the handlers it invoked, in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry<T> {
+    pub params: Vec<CallbackParams<T>>,
+    pub ids: Vec<HandlerId>,
+}
+
+/// A registered handler slot, holding a weak reference to the handler and its id.
+pub struct HandlerEntry<'a, T: 'a> {
+    pub id: HandlerId,
+    pub handler: Weak<Mutex<CallbackHandler<'a, T>>>,
+}
+
+impl<'a, T: 'a> Clone for HandlerEntry<'a, T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            handler: Weak::clone(&self.handler),
+        }
     }
+}
 
-    fn func1(p1: i32) {
-        unsafe { OUTPUT.push(format!("calling func1: {}", p1)); }
+/// How `run_all_with_policy` treats a param count that differs from the number of active handlers.
+///
+/// Params are always paired with handlers positionally.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParamCountPolicy {
+    /// Exactly one param per active handler, as `run_all` requires.
+    #[default]
+    Exact,
+    /// At least one param per active handler. Trailing params without a handler are ignored.
+    AtLeast,
+    /// At most one param per active handler. Trailing handlers without a param are not invoked.
+    AtMost,
+}
+
+/// Options and results of a single dispatch, threaded through the invocation loop.
+#[derive(Default)]
+struct DispatchState {
+    policy: ParamCountPolicy,
+    invoked: Option<Vec<HandlerId>>,
+    /// Number of handler calls made so far, a `CatchAll` handler being called once per param.
+    invocations: usize,
+    budget: Option<Duration>,
+    over_budget: Vec<HandlerId>,
+    vetoed: Vec<HandlerId>,
+    deadline: Option<Instant>,
+    skipped: Vec<HandlerId>,
+    /// Errors of single handlers with their index in dispatch order, collected instead of stopping the dispatch
+    /// when an error sink is set or for `run_all_lenient`.
+    errors: Option<Vec<(usize, CallbackError)>>,
+    /// Pairs params with handlers without checking them up front, for `run_all_lenient`. Requires `errors`.
+    lenient: bool,
+    /// A handler to move in front of the others for this dispatch only.
+    first: Option<HandlerId>,
+    /// Addresses of the handlers invoked so far, when a handler registered twice runs only once.
+    seen: Option<HashSet<*const ()>>,
+    /// Ids of the managers this event was already dispatched on, shared down the chained managers.
+    visited: HashSet<u64>,
+}
+
+/// Moves the handler at `index` in front of the others for a single dispatch.
+///
+/// The order is restored when this is dropped, so a panicking handler doesn't leave the manager reordered.
+struct Prioritized<'m, 'a, T: 'a> {
+    manager: &'m mut CallbackManager<'a, T>,
+    index: Option<usize>,
+}
+
+impl<'m, 'a, T: 'a> Prioritized<'m, 'a, T> {
+    fn new(manager: &'m mut CallbackManager<'a, T>, index: Option<usize>) -> Self {
+        if let Some(index) = index {
+            manager.handlers[..=index].rotate_right(1);
+        }
+        Self { manager, index }
     }
+}
 
-    fn func2(p1: i32, p2: i32) {
-        unsafe { OUTPUT.push(format!("calling func2: {}, {}", p1, p2)); }
+impl<'a, T: 'a> Drop for Prioritized<'_, 'a, T> {
+    fn drop(&mut self) {
+        if let Some(index) = self.index {
+            self.manager.handlers[..=index].rotate_left(1);
+        }
     }
+}
 
-    fn func3(p1: i32, p2: i32, p3: i32) {
-        unsafe { OUTPUT.push(format!("calling func3: {}, {}, {}", p1, p2, p3)); }
+impl DispatchState {
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
     }
 
-    fn func4(p1: i32, p2: i32, p3: i32, p4: i32) {
-        unsafe { OUTPUT.push(format!("calling func4: {}, {}, {}, {}", p1, p2, p3, p4)); }
+    /// Returns whether the handler was already invoked in this dispatch under another entry, marking it as seen.
+    fn is_duplicate<H>(&mut self, handler: &Arc<H>) -> bool {
+        self.seen.as_mut().is_some_and(|seen| !seen.insert(Arc::as_ptr(handler).cast()))
     }
 
-    #[test]
-    #[allow(unused_variables)]
-    fn test_callback_manager() {
-        let mut callback_manager = CallbackManager::<i32>::new();
-        assert_eq!(callback_manager.handlers.len(), 0);
-        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(func0)));
-        let h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(func1)));
-        {
-            let h3 = callback_manager.add(CallbackHandler::Callback2(Box::new(func2)));
+    /// Collects the error of a single handler to carry on with the others, or returns it if errors aren't collected.
+    fn recover(&mut self, index: usize, error: CallbackError) -> Result<(), CallbackError> {
+        match self.errors.as_mut() {
+            Some(errors) => {
+                errors.push((index, error));
+                Ok(())
+            },
+            None => Err(error),
         }
-        let h4 = callback_manager.add(CallbackHandler::Callback3(Box::new(func3)));
-        let h5 = callback_manager.add(CallbackHandler::Callback4(Box::new(func4)));
+    }
+}
+
+type StrongHandler<'a, T> = Arc<Mutex<CallbackHandler<'a, T>>>;
+
+type OrderKeyFn = Box<dyn Fn(&HandlerInfo) -> i64 + Send>;
+
+type ValidatorFn<'a, T> = Box<dyn FnMut(&[T]) -> bool + Send + 'a>;
+
+type ErrorSinkFn<'a> = Box<dyn FnMut(CallbackError) + Send + 'a>;
+
+type ExpiryFn<'a> = Box<dyn FnMut(HandlerId) + Send + 'a>;
+
+/// The times each handler was last invoked, at most `capacity` per handler, oldest first.
+struct InvocationLog {
+    capacity: usize,
+    times: HashMap<HandlerId, VecDeque<Instant>>,
+}
+
+/// Replays remembered params to a newly added handler. Only set for `Copy` arguments, see `set_replay_last`.
+/// The id of the next manager created, which tells managers apart in a chain without locking them.
+static NEXT_MANAGER_ID: AtomicU64 = AtomicU64::new(0);
+
+type ReplayFn<'a, T> = fn(&[CallbackParams<T>], &mut CallbackHandler<'a, T>);
+
+/// A callback manager struct which holds and triggers collback handlers.
+pub struct CallbackManager<'a, T: 'a> {
+    pub handlers: Vec<HandlerEntry<'a, T>>,
+    catch_all: Vec<HandlerEntry<'a, T>>,
+    next_id: u64,
+    id_base: u64,
+    manager_id: u64,
+    trace: Option<Vec<TraceEntry<T>>>,
+    order_by: Option<OrderKeyFn>,
+    /// Chained managers with their ids, read when chaining so that a cycle is detected before locking.
+    chained: Vec<(u64, Weak<Mutex<CallbackManager<'a, T>>>)>,
+    replay: Option<ReplayFn<'a, T>>,
+    last_params: Vec<CallbackParams<T>>,
+    observer: Option<Sender<DispatchEvent>>,
+    /// Shared with the registrars, which check it before queuing a handler.
+    frozen: Arc<AtomicBool>,
+    validators: HashMap<HandlerId, Mutex<ValidatorFn<'a, T>>>,
+    vetoed: Vec<HandlerId>,
+    keys: HashMap<HandlerId, Box<dyn Any + Send>>,
+    rate_limited: HashMap<HandlerId, Arc<AtomicUsize>>,
+    error_sink: Option<ErrorSinkFn<'a>>,
+    pending: registrar::PendingHandlers<'a, T>,
+    remembered: Option<HashMap<HandlerId, CallbackParams<T>>>,
+    invocation_log: Option<InvocationLog>,
+    on_expiry: Option<ExpiryFn<'a>>,
+    dedupe: bool,
+    #[cfg(feature = "test-util")]
+    order_recorder: Option<Arc<std::sync::Mutex<Vec<HandlerId>>>>,
+}
+
+impl<'a, T: 'a> Default for CallbackManager<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: 'a> CallbackManager<'a, T> {
+    /// Creates a new `CallbackManager` instance.
+    /// 
+    /// # Examples
+    /// ```
+    /// let _cb_manager = callback_manager::CallbackManager::<i32>::new();
+    /// 
+    /// assert_eq!(_cb_manager.handlers.len(), 0);
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            handlers: vec![],
+            catch_all: vec![],
+            next_id: 0,
+            id_base: 0,
+            manager_id: NEXT_MANAGER_ID.fetch_add(1, Ordering::Relaxed),
+            trace: None,
+            order_by: None,
+            chained: vec![],
+            replay: None,
+            last_params: vec![],
+            observer: None,
+            frozen: Arc::default(),
+            validators: HashMap::new(),
+            vetoed: vec![],
+            keys: HashMap::new(),
+            rate_limited: HashMap::new(),
+            error_sink: None,
+            pending: Arc::default(),
+            remembered: None,
+            invocation_log: None,
+            on_expiry: None,
+            dedupe: false,
+            #[cfg(feature = "test-util")]
+            order_recorder: None,
+        }
+    }
+
+    /// Creates a new `CallbackManager` instance with room for `capacity` handlers before reallocating.
+    ///
+    /// # Examples
+    /// ```
+    /// let cb_manager = callback_manager::CallbackManager::<i32>::with_capacity(16);
+    ///
+    /// assert!(cb_manager.capacity() >= 16);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            handlers: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a new `CallbackManager` instance whose handler ids start from `base` instead of 0.
+    ///
+    /// Ids still increase by one per registered handler, so managers given disjoint ranges never share an id.
+    ///
+    /// # Examples
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new_with_id_base(1000);
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(1000), HandlerId(1001)]);
+    /// ```
+    pub fn new_with_id_base(base: u64) -> Self {
+        Self {
+            next_id: base,
+            id_base: base,
+            ..Self::new()
+        }
+    }
+
+    /// Creates a new `CallbackManager` instance holding the given handlers, in order, and returns the handlers back.
+    ///
+    /// The manager only keeps weak references, so the returned handles must be kept alive for the handlers to stay
+    /// registered. Ids are assigned in order, starting from 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId, Mutex};
+    ///
+    /// let handlers = vec![
+    ///     Arc::new(Mutex::new(CallbackHandler::Callback0(Box::new(|| {})))),
+    ///     Arc::new(Mutex::new(CallbackHandler::Callback1(Box::new(|_x: i32| {})))),
+    /// ];
+    ///
+    /// let (cb_manager, _handlers) = CallbackManager::with_handlers(handlers);
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(0), HandlerId(1)]);
+    /// ```
+    pub fn with_handlers(handlers: Vec<Arc<Mutex<CallbackHandler<'a, T>>>>) -> (Self, Vec<Arc<Mutex<CallbackHandler<'a, T>>>>) {
+        let mut manager = Self::with_capacity(handlers.len());
+        for handler in handlers.iter() {
+            manager.push_entry(Arc::downgrade(handler));
+        }
+        (manager, handlers)
+    }
+
+    /// Creates a new `CallbackManager` instance with a no-op handler in place of each live handler, and returns the
+    /// no-op handlers.
+    ///
+    /// The no-op handlers keep the ids, order and accepted arities of the originals, so dispatch planning can be
+    /// tested without side effects. Settings like `set_order_by` or tracing aren't copied. As with `with_handlers`,
+    /// the returned handles must be kept alive for the handlers to stay registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| panic!("side effect")))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| panic!("side effect")))).unwrap();
+    ///
+    /// let (mut noop_manager, _noops) = cb_manager.clone_structure_with_noops();
+    ///
+    /// assert_eq!(noop_manager.snapshot(), cb_manager.snapshot());
+    /// assert!(noop_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).is_ok());
+    /// ```
+    pub fn clone_structure_with_noops(&self) -> (Self, Vec<Arc<Mutex<CallbackHandler<'a, T>>>>) {
+        let mut manager = Self::with_capacity(self.handlers.len());
+        let mut noops = vec![];
+        for (entries, manager_entries) in [(&self.handlers, &mut manager.handlers), (&self.catch_all, &mut manager.catch_all)] {
+            for entry in entries.iter() {
+                if let Some(mutex_handler) = entry.handler.upgrade() {
+                    let noop = Arc::new(Mutex::new(sync::lock_ignoring_poison(&mutex_handler).noop_like()));
+                    manager_entries.push(HandlerEntry { id: entry.id, handler: Arc::downgrade(&noop) });
+                    noops.push(noop);
+                }
+            }
+        }
+        manager.next_id = self.next_id;
+        manager.id_base = self.id_base;
+        (manager, noops)
+    }
+
+    /// Moves the live handlers from dispatch index `index` onward into a new manager, leaving the earlier ones in
+    /// `self`, like `Vec::split_off`.
+    ///
+    /// Dropped handlers are pruned from `self` first, so `index` counts live handlers only and no dropped handler
+    /// is carried over. The moved handlers keep their ids, validators and keys, and both managers keep allocating
+    /// ids after the highest one used so far. `CatchAll` handlers and settings like `set_order_by` or tracing stay
+    /// with `self`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of live handlers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let other_manager = cb_manager.split_off(1).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(0)]);
+    /// assert_eq!(other_manager.active_ids(), vec![HandlerId(1)]);
+    /// ```
+    pub fn split_off(&mut self, index: usize) -> Result<Self, CallbackError> {
+        self.check_not_frozen()?;
+        self.drop_inactive();
+        let mut manager = Self::new();
+        manager.handlers = self.handlers.split_off(index);
+        for entry in manager.handlers.iter() {
+            if let Some(validator) = self.validators.remove(&entry.id) {
+                manager.validators.insert(entry.id, validator);
+            }
+            if let Some(key) = self.keys.remove(&entry.id) {
+                manager.keys.insert(entry.id, key);
+            }
+            if let Some(dropped) = self.rate_limited.remove(&entry.id) {
+                manager.rate_limited.insert(entry.id, dropped);
+            }
+        }
+        manager.next_id = self.next_id;
+        manager.id_base = self.id_base;
+        Ok(manager)
+    }
+
+    /// Returns the number of handlers the manager can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.handlers.capacity()
+    }
+
+    /// Returns a rough estimate of the bytes used by the manager and its live handlers, to track growth over time.
+    ///
+    /// This counts the entry vectors at their full capacity, plus the shared allocation of each live handler: its
+    /// reference counts, mutex and `CallbackHandler`. It excludes the state captured by the closures, which can't
+    /// be measured, as well as the boxes holding them and the manager's bookkeeping for validators, keys and
+    /// tracing. It's a diagnostic aid, not an exact figure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let empty = cb_manager.estimated_memory();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.estimated_memory() > empty);
+    /// ```
+    pub fn estimated_memory(&self) -> usize {
+        let entries = (self.handlers.capacity() + self.catch_all.capacity()) * std::mem::size_of::<HandlerEntry<'a, T>>();
+        let live = self.handlers.iter().chain(&self.catch_all).filter(|entry| entry.handler.strong_count() > 0).count();
+        // An `Arc` allocation holds the strong and weak counts next to the value.
+        let per_handler = 2 * std::mem::size_of::<usize>() + std::mem::size_of::<Mutex<CallbackHandler<'a, T>>>();
+        std::mem::size_of::<Self>() + entries + live * per_handler
+    }
+
+    /// Reserves room for at least `additional` more handlers, so that a known burst of `add` calls doesn't
+    /// reallocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    ///
+    /// cb_manager.reserve(16);
+    ///
+    /// assert!(cb_manager.capacity() >= 16);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.handlers.reserve(additional);
+    }
+
+    /// Adds a new callback handler.
+    ///
+    /// Takes amortized O(1) time: when `handlers` is full it is reallocated with a geometrically larger
+    /// capacity, moving every entry. Use `with_capacity` or `reserve` to avoid this during bulk registration.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// fn print(x: i32) {
+    ///     println!("number is {x}");
+    /// }
+    /// 
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    /// 
+    /// cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(print))).unwrap();
+    /// 
+    /// assert_eq!(cb_manager.handlers.len(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This is the fallible entry point for registration: it fails with `CallbackError::Frozen` if the manager is
+    /// frozen, see `freeze`. The manager has no cap on the number of handlers, so this is the only failure.
+    pub fn add(&mut self, mut handler: CallbackHandler<'a, T>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        self.check_not_frozen()?;
+        if let Some(replay) = self.replay {
+            replay(&self.last_params, &mut handler);
+        }
+        let strong_handler = Arc::new(Mutex::new(handler));
+        self.push_entry(Arc::downgrade(&strong_handler));
+        Ok(strong_handler)
+    }
+
+    /// Adds a new callback handler like `add`, also returning the id it was registered with.
+    ///
+    /// Ids come from a counter on the manager and are never reused, even after the handler is removed, so they
+    /// can key external state about the handler. See `remove_by_id`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let (id, _handler) = cb_manager.add_with_id(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert_eq!(id, HandlerId(0));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    pub fn add_with_id(&mut self, handler: CallbackHandler<'a, T>) -> Result<(HandlerId, StrongHandler<'a, T>), CallbackError> {
+        let handler = self.add(handler)?;
+        Ok((HandlerId(self.next_id - 1), handler))
+    }
+
+    /// Adds a handler whose lifetime is managed by the caller, returning its id.
+    ///
+    /// The manager never holds a strong reference to it: the handler is dispatched until the last `Arc` the
+    /// caller keeps elsewhere is dropped, and disappears from dispatch afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, Mutex};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = Arc::new(Mutex::new(CallbackHandler::Callback0(Box::new(|| {}))));
+    /// cb_manager.add_weak(Arc::downgrade(&handler)).unwrap();
+    /// assert_eq!(cb_manager.active_count(), 1);
+    ///
+    /// drop(handler);
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    pub fn add_weak(&mut self, weak: Weak<Mutex<CallbackHandler<'a, T>>>) -> Result<HandlerId, CallbackError> {
+        self.check_not_frozen()?;
+        Ok(self.register_weak(weak))
+    }
+
+    fn register_weak(&mut self, weak: Weak<Mutex<CallbackHandler<'a, T>>>) -> HandlerId {
+        if let Some(mutex_handler) = weak.upgrade() {
+            if let (Some(replay), Ok(mut guard_handler)) = (self.replay, sync::lock(&mutex_handler)) {
+                replay(&self.last_params, &mut guard_handler);
+            }
+        }
+        self.push_entry(weak)
+    }
+
+    /// Stops any further handler registration or removal. Dispatch keeps working as before.
+    ///
+    /// Afterwards `add`, `remove` and every other method registering or removing handlers, like `remove_by_id`,
+    /// `pop`, `retain_by_id` or `clear`, return `CallbackError::Frozen` and leave the handlers untouched. A handler
+    /// whose last handle is dropped still stops being dispatched. A frozen manager can't be unfrozen.
+    ///
+    /// Handlers already queued through a registrar are registered by `freeze`, and `Registrar::add` fails with
+    /// `CallbackError::Frozen` afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackError, CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// cb_manager.freeze();
+    ///
+    /// assert!(cb_manager.is_frozen());
+    /// assert_eq!(cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).err(), Some(CallbackError::Frozen));
+    /// assert_eq!(cb_manager.pop().err(), Some(CallbackError::Frozen));
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams0()]).is_ok());
+    /// ```
+    pub fn freeze(&mut self) {
+        let pending = {
+            let mut pending = self.pending.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            // Registrars check the flag under the same lock, so their handlers are either taken here or refused.
+            self.frozen.store(true, Ordering::Relaxed);
+            std::mem::take(&mut *pending)
+        };
+        for weak in pending {
+            self.register_weak(weak);
+        }
+    }
+
+    /// Returns whether the manager was frozen with `freeze`.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Relaxed)
+    }
+
+    fn check_not_frozen(&self) -> Result<(), CallbackError> {
+        if self.is_frozen() {
+            return Err(CallbackError::Frozen);
+        }
+        Ok(())
+    }
+
+    fn push_entry(&mut self, handler: Weak<Mutex<CallbackHandler<'a, T>>>) -> HandlerId {
+        let id = HandlerId(self.next_id);
+        self.next_id += 1;
+        #[cfg(feature = "log")]
+        let capacity = self.handlers.capacity();
+        let is_catch_all = handler.upgrade().is_some_and(
+            |mutex_handler| matches!(*sync::lock_ignoring_poison(&mutex_handler), CallbackHandler::CatchAll(_))
+        );
+        if is_catch_all {
+            self.catch_all.push(HandlerEntry { id, handler });
+            return id;
+        }
+        self.handlers.push(HandlerEntry { id, handler });
+        #[cfg(feature = "log")]
+        if self.handlers.capacity() != capacity {
+            log::trace!("adding handler {} grew handler capacity from {} to {}", id.0, capacity, self.handlers.capacity());
+        }
+        id
+    }
+
+    /// Adds a new callback handler at `index` in dispatch order, shifting the handlers after it.
+    ///
+    /// `index` counts every entry in `handlers`, including dropped handlers not yet cleaned up, and is clamped to
+    /// the number of entries. Positional param vectors passed to `run_all` must follow the new order. The handler
+    /// still gets the next id, so ids no longer increase in dispatch order; ids already handed out are unchanged.
+    /// An order set with `set_order_by` takes precedence at dispatch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.insert(0, CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(1), HandlerId(0)]);
+    /// ```
+    pub fn insert(&mut self, index: usize, handler: CallbackHandler<'a, T>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let strong_handler = self.add(handler)?;
+        if self.handlers.last().is_some_and(|entry| std::ptr::eq(entry.handler.as_ptr(), Arc::as_ptr(&strong_handler))) {
+            let entry = self.handlers.pop().expect("checked above");
+            self.handlers.insert(index.min(self.handlers.len()), entry);
+        }
+        Ok(strong_handler)
+    }
+
+    /// Adds a handler of the given arity that does nothing, reserving its id and dispatch position.
+    ///
+    /// The real handler can be installed later by locking the returned handle and assigning it in place, keeping
+    /// the same arity so positional params stay valid. Fails with `CallbackError::InvalidArity` if `arity` is
+    /// greater than 12.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _placeholder = cb_manager.add_noop(2).unwrap();
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).is_ok());
+    /// assert!(cb_manager.add_noop(13).is_err());
+    /// ```
+    pub fn add_noop(&mut self, arity: usize) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let handler = CallbackHandler::noop(arity).ok_or(CallbackError::InvalidArity { arity })?;
+        self.add(handler)
+    }
+
+    /// Adds a new callback handler under a caller-chosen key, which `remove_by_key` can later remove it by.
+    ///
+    /// Keys don't have to be unique.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add_keyed("logger", CallbackHandler::Callback1(Box::new(|x| println!("{x}")))).unwrap();
+    ///
+    /// assert!(cb_manager.remove_by_key(&"logger").unwrap());
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// ```
+    pub fn add_keyed<K: Eq + Send + 'static>(&mut self, key: K, handler: CallbackHandler<'a, T>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let handler = self.add(handler)?;
+        self.keys.insert(HandlerId(self.next_id - 1), Box::new(key));
+        Ok(handler)
+    }
+
+    /// Removes every handler added with `add_keyed` under a key equal to `key`, returning whether any was removed.
+    ///
+    /// Only keys of the same type as `key` can match. The removed handlers are no longer dispatched, even while
+    /// their handles are alive. Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    pub fn remove_by_key<K: Eq + Send + 'static>(&mut self, key: &K) -> Result<bool, CallbackError> {
+        self.check_not_frozen()?;
+        let removed = self.keys.iter().filter(
+            |(_, stored)| stored.downcast_ref::<K>() == Some(key)
+        ).map(|(id, _)| *id).collect::<Vec<HandlerId>>();
+        if removed.is_empty() {
+            return Ok(false);
+        }
+        self.handlers.retain(|entry| !removed.contains(&entry.id));
+        self.catch_all.retain(|entry| !removed.contains(&entry.id));
+        self.forget_removed();
+        Ok(true)
+    }
+
+    /// Removes the handler behind `handle`, returning whether it was registered.
+    ///
+    /// The handler is no longer dispatched, even while its handles are alive. If it was registered several times,
+    /// for instance through `add_weak`, every registration is removed.
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.remove(&handler).unwrap());
+    /// assert!(!cb_manager.remove(&handler).unwrap());
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// ```
+    pub fn remove(&mut self, handle: &Arc<Mutex<CallbackHandler<'a, T>>>) -> Result<bool, CallbackError> {
+        self.check_not_frozen()?;
+        let count = self.handlers.len() + self.catch_all.len();
+        self.handlers.retain(|entry| !std::ptr::eq(entry.handler.as_ptr(), Arc::as_ptr(handle)));
+        self.catch_all.retain(|entry| !std::ptr::eq(entry.handler.as_ptr(), Arc::as_ptr(handle)));
+        if self.handlers.len() + self.catch_all.len() == count {
+            return Ok(false);
+        }
+        self.forget_removed();
+        Ok(true)
+    }
+
+    /// Removes the handler registered with this id, returning whether it was registered.
+    ///
+    /// The handler is no longer dispatched, even while its handles are alive. Its id isn't reused. Fails with
+    /// `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let (id, _handler) = cb_manager.add_with_id(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.remove_by_id(id).unwrap());
+    /// assert!(!cb_manager.remove_by_id(id).unwrap());
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// ```
+    pub fn remove_by_id(&mut self, id: HandlerId) -> Result<bool, CallbackError> {
+        self.check_not_frozen()?;
+        let count = self.handlers.len() + self.catch_all.len();
+        self.handlers.retain(|entry| entry.id != id);
+        self.catch_all.retain(|entry| entry.id != id);
+        if self.handlers.len() + self.catch_all.len() == count {
+            return Ok(false);
+        }
+        self.forget_removed();
+        Ok(true)
+    }
+
+    /// Reassigns the ids of the live handlers densely from the manager's id base, 0 unless created with
+    /// `new_with_id_base`, and returns the mapping from old to new ids.
+    ///
+    /// New ids follow the order of the old ones, and later handlers get ids after them. Dropped handlers are
+    /// pruned first. Dispatch order is unchanged, and validators, keys and the other settings kept per handler
+    /// follow their handler to its new id. Ids already recorded elsewhere, like in traces or dispatch events, are
+    /// not rewritten.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// drop(cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// let mapping = cb_manager.compact();
+    ///
+    /// assert_eq!(mapping[&HandlerId(1)], HandlerId(0));
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(0)]);
+    /// ```
+    pub fn compact(&mut self) -> HashMap<HandlerId, HandlerId> {
+        self.drop_inactive();
+        let mut ids = self.handlers.iter().chain(&self.catch_all).map(|entry| entry.id).collect::<Vec<HandlerId>>();
+        ids.sort();
+        let mapping = ids.into_iter().zip(self.id_base..).map(
+            |(old, new)| (old, HandlerId(new))
+        ).collect::<HashMap<HandlerId, HandlerId>>();
+
+        for entry in self.handlers.iter_mut().chain(self.catch_all.iter_mut()) {
+            entry.id = mapping[&entry.id];
+        }
+        Self::rekey(&mut self.validators, &mapping);
+        Self::rekey(&mut self.keys, &mapping);
+        Self::rekey(&mut self.rate_limited, &mapping);
+        if let Some(remembered) = self.remembered.as_mut() {
+            Self::rekey(remembered, &mapping);
+        }
+        if let Some(log) = self.invocation_log.as_mut() {
+            Self::rekey(&mut log.times, &mapping);
+        }
+        self.vetoed.retain(|id| mapping.contains_key(id));
+        self.vetoed.iter_mut().for_each(|id| *id = mapping[id]);
+        self.next_id = self.id_base + mapping.len() as u64;
+        mapping
+    }
+
+    fn rekey<V>(map: &mut HashMap<HandlerId, V>, mapping: &HashMap<HandlerId, HandlerId>) {
+        *map = map.drain().filter_map(|(id, value)| Some((*mapping.get(&id)?, value))).collect();
+    }
+
+    /// Keeps only the handlers for which `f` returns `true`, given their id and the key they were added under with
+    /// `add_keyed`.
+    ///
+    /// The key is `None` for handlers added without one or under a key of another type than `K`. Handlers are not
+    /// locked, so this can't deadlock on a handler that is being dispatched. Dropped handlers are removed without
+    /// calling `f`. Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add_keyed("stale", CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add_keyed("fresh", CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler2 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// cb_manager.retain_by_id(|_id, key: Option<&&str>| key != Some(&"stale")).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(1), HandlerId(2)]);
+    /// ```
+    pub fn retain_by_id<K: 'static>(&mut self, mut f: impl FnMut(HandlerId, Option<&K>) -> bool) -> Result<(), CallbackError> {
+        self.check_not_frozen()?;
+        let keys = &self.keys;
+        let mut keep = |entry: &HandlerEntry<'a, T>| {
+            entry.handler.strong_count() > 0 && f(entry.id, keys.get(&entry.id).and_then(|key| key.downcast_ref::<K>()))
+        };
+        self.handlers.retain(&mut keep);
+        self.catch_all.retain(keep);
+        self.forget_removed();
+        Ok(())
+    }
+
+    /// Removes the last live handler added, the one with the highest id, and returns it. Together with `add`, this
+    /// lets the manager be used as a stack of handlers, whichever kind of handler was added last.
+    ///
+    /// Dropped handlers added after it are removed on the way. Returns `None` once no live handler is left. A
+    /// popped handler is no longer dispatched, even though the returned `Arc` keeps it alive.
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// assert!(Arc::ptr_eq(&cb_manager.pop().unwrap().unwrap(), &handler1));
+    /// assert_eq!(cb_manager.active_count(), 1);
+    /// ```
+    pub fn pop(&mut self) -> Result<Option<Arc<Mutex<CallbackHandler<'a, T>>>>, CallbackError> {
+        self.check_not_frozen()?;
+        let last = |entries: &[HandlerEntry<'a, T>]| entries.iter().enumerate().max_by_key(
+            |(_, entry)| entry.id
+        ).map(|(index, entry)| (entry.id, index));
+        loop {
+            let entry = match (last(&self.handlers), last(&self.catch_all)) {
+                (Some((id, index)), Some((catch_all_id, _))) if id > catch_all_id => self.handlers.remove(index),
+                (_, Some((_, index))) => self.catch_all.remove(index),
+                (Some((_, index)), None) => self.handlers.remove(index),
+                (None, None) => {
+                    self.forget_removed();
+                    return Ok(None);
+                },
+            };
+            if let Some(handler) = entry.handler.upgrade() {
+                self.forget_removed();
+                return Ok(Some(handler));
+            }
+        }
+    }
+
+    /// Adds a batch of handlers only if their arities match `template`, entry for entry.
+    ///
+    /// Either every handler is registered, in order, or none is and `CallbackError::TemplateMismatch` is returned.
+    /// Fails with `CallbackError::Frozen` if the manager is frozen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackError, CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let result = cb_manager.add_batch_checked(vec![
+    ///     CallbackHandler::Callback0(Box::new(|| {})),
+    ///     CallbackHandler::Callback1(Box::new(|_x| {})),
+    /// ], &[0, 2]);
+    ///
+    /// assert_eq!(result.err(), Some(CallbackError::TemplateMismatch { expected: vec![0, 2], actual: vec![0, 1] }));
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// ```
+    pub fn add_batch_checked(&mut self, handlers: Vec<CallbackHandler<'a, T>>, template: &[usize]) -> Result<Vec<Arc<Mutex<CallbackHandler<'a, T>>>>, CallbackError> {
+        self.check_not_frozen()?;
+        let arities = handlers.iter().map(CallbackHandler::arity).collect::<Vec<usize>>();
+        if arities != template {
+            return Err(CallbackError::TemplateMismatch { expected: template.to_vec(), actual: arities });
+        }
+        self.extend_returning(handlers)
+    }
+
+    /// Adds every handler from the iterator and returns their strong handles, in iteration order.
+    ///
+    /// As with `add`, each handler stays registered only while its returned handle is kept alive. Fails with
+    /// `CallbackError::Frozen` if the manager is frozen, before registering any handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handlers = cb_manager.extend_returning(vec![
+    ///     CallbackHandler::Callback0(Box::new(|| {})),
+    ///     CallbackHandler::Callback1(Box::new(|_x| {})),
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_count(), 2);
+    /// ```
+    pub fn extend_returning<I: IntoIterator<Item = CallbackHandler<'a, T>>>(&mut self, iter: I) -> Result<Vec<Arc<Mutex<CallbackHandler<'a, T>>>>, CallbackError> {
+        iter.into_iter().map(|handler| self.add(handler)).collect()
+    }
+
+    /// Unregisters every handler and registers `handlers` in their place, returning their strong handles in order.
+    ///
+    /// The old handlers are no longer dispatched, even while their handles are alive, and their validators and keys
+    /// are dropped. Nothing is dispatched between the removal and the registration, as both happen under the same
+    /// `&mut self`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`. The existing handlers are left
+    /// registered in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _old = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _new = cb_manager.replace_all(vec![
+    ///     CallbackHandler::Callback1(Box::new(|_x| {})),
+    ///     CallbackHandler::Callback2(Box::new(|_x, _y| {})),
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(cb_manager.snapshot().iter().map(|(_, arity)| *arity).collect::<Vec<usize>>(), vec![1, 2]);
+    /// ```
+    pub fn replace_all(&mut self, handlers: Vec<CallbackHandler<'a, T>>) -> Result<Vec<Arc<Mutex<CallbackHandler<'a, T>>>>, CallbackError> {
+        self.check_not_frozen()?;
+        self.handlers.clear();
+        self.catch_all.clear();
+        self.validators.clear();
+        self.keys.clear();
+        self.rate_limited.clear();
+        self.extend_returning(handlers)
+    }
+
+    /// Removes every handler, including those queued by registrars and not yet dispatched.
+    ///
+    /// The manager only holds weak references, so this doesn't free the handlers: handles kept by the caller stay
+    /// valid, but their handlers are no longer dispatched. Settings kept per handler, like validators and keys, are
+    /// dropped with them, while manager-wide settings are kept and ids are not reused.
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// cb_manager.clear().unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_count(), 0);
+    /// assert_eq!(std::sync::Arc::strong_count(&handler), 1);
+    /// ```
+    pub fn clear(&mut self) -> Result<(), CallbackError> {
+        self.check_not_frozen()?;
+        self.handlers.clear();
+        self.catch_all.clear();
+        self.pending.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clear();
+        self.forget_removed();
+        Ok(())
+    }
+
+    /// Returns active handler counts.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    /// 
+    /// let _handler = cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(|x: i32| {println!("number is {x}")}))).unwrap();
+    /// 
+    /// assert_eq!(cb_manager.active_count(), 1);
+    /// ```
+    pub fn active_count(&self) -> usize {
+        self.handlers.iter().filter(|entry| entry.handler.upgrade().is_some()).count()
+    }
+
+    /// Counts the active, dead and poisoned handlers in a single pass.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, ManagerStats};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// drop(cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+    ///
+    /// let stats = cb_manager.len_by_state();
+    /// assert_eq!(stats, ManagerStats { active: 1, dead: 1, poisoned: 0 });
+    /// assert_eq!(stats.to_string(), "1 active (0 poisoned), 1 dead");
+    /// ```
+    pub fn len_by_state(&self) -> ManagerStats {
+        let mut stats = ManagerStats::default();
+        for entry in self.handlers.iter() {
+            match entry.handler.upgrade() {
+                Some(mutex_handler) => {
+                    stats.active += 1;
+                    if sync::is_poisoned(&mutex_handler) {
+                        stats.poisoned += 1;
+                    }
+                },
+                None => stats.dead += 1,
+            }
+        }
+        stats
+    }
+
+    /// Describes the live handlers in dispatch order, see `ManagerDescription`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap();
+    ///
+    /// let description = cb_manager.describe();
+    /// assert_eq!(description.handlers[0].id, HandlerId(0));
+    /// assert_eq!(description.handlers[0].arity, 2);
+    /// ```
+    pub fn describe(&self) -> ManagerDescription {
+        let handlers = self.dispatch_order().into_iter().filter_map(|id| {
+            let (entry, catch_all) = match self.handlers.iter().find(|entry| entry.id == id) {
+                Some(entry) => (entry, false),
+                None => (self.catch_all.iter().find(|entry| entry.id == id)?, true),
+            };
+            let mutex_handler = entry.handler.upgrade()?;
+            let arity = sync::lock_ignoring_poison(&mutex_handler).arity();
+            Some(HandlerDescription {
+                id,
+                arity,
+                catch_all,
+                poisoned: sync::is_poisoned(&mutex_handler),
+                recent_invocations: self.invocation_log.as_ref().map(
+                    |log| log.times.get(&id).map_or(0, VecDeque::len)
+                ),
+            })
+        }).collect();
+        ManagerDescription { handlers, frozen: self.is_frozen() }
+    }
+
+    /// Returns how many live handlers have a mutex poisoned by a panicking callback.
+    ///
+    /// Always 0 with the `parking_lot` feature, whose mutexes don't poison.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(callback_manager::CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.poisoned_count(), 0);
+    /// ```
+    pub fn poisoned_count(&self) -> usize {
+        self.handlers.iter().filter(
+            |entry| entry.handler.upgrade().is_some_and(|mutex_handler| sync::is_poisoned(&mutex_handler))
+        ).count()
+    }
+
+    /// Returns whether the handle is registered with this manager.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let mut other_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.is_active(&handler));
+    /// assert!(!other_manager.is_active(&handler));
+    /// ```
+    pub fn is_active(&self, handle: &Arc<Mutex<CallbackHandler<'a, T>>>) -> bool {
+        self.handlers.iter().chain(&self.catch_all).any(|entry| std::ptr::eq(entry.handler.as_ptr(), Arc::as_ptr(handle)))
+    }
+
+    /// Returns the position of the handle in `handlers`, for use with index-based methods like `get` or `insert`,
+    /// or `None` if it isn't registered with this manager.
+    ///
+    /// `CatchAll` handlers are kept apart from `handlers` and have no position, so this returns `None` for them
+    /// too; `is_active` tells whether they are registered. Positions shift when handlers are added, removed or
+    /// reordered, including when a dispatch prunes dropped handlers or applies `set_order_by`, so the result is
+    /// only valid until the next mutation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let index = cb_manager.index_of(&handler1).unwrap();
+    /// assert_eq!(index, 1);
+    /// assert!(Arc::ptr_eq(&cb_manager.get(index).unwrap(), &handler1));
+    /// ```
+    pub fn index_of(&self, handle: &Arc<Mutex<CallbackHandler<'a, T>>>) -> Option<usize> {
+        self.handlers.iter().position(|entry| std::ptr::eq(entry.handler.as_ptr(), Arc::as_ptr(handle)))
+    }
+
+    /// Returns the number of strong references to the handler, a thin wrapper over `Arc::strong_count`.
+    ///
+    /// Managers only hold weak references, so this counts the `Arc`s kept by callers. A count of 1 means `handle`
+    /// is the last one: dropping it unregisters the handler from every manager it was added to. A larger count
+    /// means clones of the handle, or a handle shared through `add_weak`, keep it registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// assert_eq!(CallbackManager::strong_count(&handler), 1);
+    ///
+    /// let clone = Arc::clone(&handler);
+    /// assert_eq!(CallbackManager::strong_count(&handler), 2);
+    ///
+    /// drop(clone);
+    /// assert_eq!(CallbackManager::strong_count(&handler), 1);
+    /// ```
+    pub fn strong_count(handle: &Arc<Mutex<CallbackHandler<'a, T>>>) -> usize {
+        Arc::strong_count(handle)
+    }
+
+    /// Returns the ids of the live handlers, in registration order, with `CatchAll` handlers after the others.
+    ///
+    /// No handler is invoked or locked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler2 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.active_ids(), vec![HandlerId(0), HandlerId(2)]);
+    /// ```
+    pub fn active_ids(&self) -> Vec<HandlerId> {
+        self.handlers.iter().chain(&self.catch_all).filter(|entry| entry.handler.strong_count() > 0).map(|entry| entry.id).collect()
+    }
+
+    /// Returns the id and arity of each live handler, in registration order.
+    ///
+    /// The closures themselves can't be captured, but the snapshot can be compared with `verify_against` after
+    /// re-registering handlers to detect drift between the expected and actual registrations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap();
+    ///
+    /// let snapshot = cb_manager.snapshot();
+    /// assert_eq!(snapshot, vec![(HandlerId(0), 0), (HandlerId(1), 2)]);
+    /// assert!(cb_manager.verify_against(&snapshot).is_ok());
+    /// ```
+    pub fn snapshot(&self) -> Vec<(HandlerId, usize)> {
+        self.live_arities().collect()
+    }
+
+    /// Returns the largest arity among live handlers, or `None` if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// assert_eq!(cb_manager.max_arity(), None);
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler2 = cb_manager.add(CallbackHandler::Callback3(Box::new(|_x, _y, _z| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.max_arity(), Some(3));
+    /// assert_eq!(cb_manager.min_arity(), Some(0));
+    /// ```
+    pub fn max_arity(&self) -> Option<usize> {
+        self.live_arities().map(|(_, arity)| arity).max()
+    }
+
+    /// Returns the smallest arity among live handlers, or `None` if there are none.
+    ///
+    /// See `max_arity` for an example.
+    pub fn min_arity(&self) -> Option<usize> {
+        self.live_arities().map(|(_, arity)| arity).min()
+    }
+
+    /// Counts the live handlers whose positionally paired param in `params` has an arity they don't accept.
+    ///
+    /// Params are paired with the live handlers in their current order, as `run_all` would. Surplus params or
+    /// handlers aren't counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.count_mismatches(&[CallbackParams::CallParams0(), CallbackParams::CallParams0()]), 1);
+    /// ```
+    pub fn count_mismatches(&self, params: &[CallbackParams<T>]) -> usize {
+        self.handlers.iter().filter_map(|entry| entry.handler.upgrade()).zip(params).filter(
+            |(mutex_handler, param)| !sync::lock_ignoring_poison(mutex_handler).accepts(param.arity())
+        ).count()
+    }
+
+    fn live_arities(&self) -> impl Iterator<Item = (HandlerId, usize)> + use<'_, 'a, T> {
+        self.handlers.iter().filter_map(
+            |entry| entry.handler.upgrade().map(
+                |mutex_handler| (entry.id, sync::lock_ignoring_poison(&mutex_handler).arity())
+            )
+        )
+    }
+
+    /// Checks that the live handlers match a snapshot taken with `snapshot`.
+    pub fn verify_against(&self, snapshot: &[(HandlerId, usize)]) -> Result<(), CallbackError> {
+        let actual = self.snapshot();
+        if actual != snapshot {
+            return Err(CallbackError::SnapshotMismatch { expected: snapshot.to_vec(), actual });
+        }
+        Ok(())
+    }
+
+    /// Returns the handler at `index` in `handlers`, or `None` if the index is out of bounds or the handler was dropped.
+    ///
+    /// The manager doesn't implement `Index`, since it only holds weak references and `Index` can't return the
+    /// upgraded `Arc` by value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(callback_manager::CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.get(0).is_some());
+    /// assert!(cb_manager.get(1).is_none());
+    /// ```
+    pub fn get(&self, index: usize) -> Option<Arc<Mutex<CallbackHandler<'a, T>>>> {
+        self.handlers.get(index)?.handler.upgrade()
+    }
+
+    /// Returns the handler at `index` in `handlers`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds or the handler was dropped.
+    pub fn get_expect(&self, index: usize) -> Arc<Mutex<CallbackHandler<'a, T>>> {
+        let entry = self.handlers.get(index).unwrap_or_else(
+            || panic!("handler index out of bounds: the len is {} but the index is {}", self.handlers.len(), index)
+        );
+        entry.handler.upgrade().unwrap_or_else(|| panic!("handler {} at index {} has been dropped", entry.id.0, index))
+    }
+
+    /// Returns handles of the live handlers whose info matches the predicate, in registration order.
+    ///
+    /// Dropped handlers and handlers with a poisoned mutex are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.find_all(|info| info.arity == 1).len(), 1);
+    /// ```
+    pub fn find_all<F: Fn(&HandlerInfo) -> bool>(&self, f: F) -> Vec<Arc<Mutex<CallbackHandler<'a, T>>>> {
+        self.handlers.iter().filter_map(
+            |entry| Self::live_info(entry).filter(|(_, info)| f(info)).map(|(handler, _)| handler)
+        ).collect()
+    }
+
+    fn live_info(entry: &HandlerEntry<'a, T>) -> Option<(Arc<Mutex<CallbackHandler<'a, T>>>, HandlerInfo)> {
+        let mutex_handler = entry.handler.upgrade()?;
+        let arity = sync::lock(&mutex_handler).ok()?.arity();
+        Some((mutex_handler, HandlerInfo { id: entry.id, arity }))
+    }
+
+    /// Removes the handlers whose handles were all dropped, reporting them to the expiry callback if one is set.
+    ///
+    /// Dispatching does this too, so calling it is only needed to notice expired handlers, or free their entries,
+    /// without dispatching.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let expired = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let expired_clone = Arc::clone(&expired);
+    /// cb_manager.set_expiry_callback(move |id| expired_clone.lock().unwrap().push(id));
+    ///
+    /// drop(cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+    /// cb_manager.prune();
+    ///
+    /// assert_eq!(*expired.lock().unwrap(), vec![HandlerId(0)]);
+    /// assert_eq!(cb_manager.handlers.len(), 0);
+    /// ```
+    pub fn prune(&mut self) {
+        self.drop_inactive();
+    }
+
+    /// Sets a callback invoked with the id of each handler found expired, because all its handles were dropped,
+    /// when the manager removes it.
+    ///
+    /// Expired handlers are removed by `prune` and at the start of every dispatch, and each one is reported once.
+    /// This replaces any previous expiry callback.
+    pub fn set_expiry_callback(&mut self, callback: impl FnMut(HandlerId) + Send + 'a) {
+        self.on_expiry = Some(Box::new(callback));
+    }
+
+    fn drop_inactive(&mut self) {
+        // Every dispatch starts here, which is when handlers added through registrars become visible.
+        self.flush_pending();
+        if let Some(on_expiry) = self.on_expiry.as_mut() {
+            self.handlers.iter().chain(&self.catch_all).filter(
+                |entry| entry.handler.strong_count() == 0
+            ).for_each(|entry| on_expiry(entry.id));
+        }
+        let count = self.handlers.len() + self.catch_all.len();
+        self.handlers.retain(|entry| entry.handler.strong_count() > 0);
+        self.catch_all.retain(|entry| entry.handler.strong_count() > 0);
+        if self.handlers.len() + self.catch_all.len() != count {
+            self.forget_removed();
+        }
+    }
+
+    /// Drops the validators, keys, drop counters, remembered params and invocation times of handlers no longer
+    /// registered.
+    fn forget_removed(&mut self) {
+        let remembered = self.remembered.as_ref().is_some_and(|remembered| !remembered.is_empty());
+        let logged = self.invocation_log.as_ref().is_some_and(|log| !log.times.is_empty());
+        if self.validators.is_empty() && self.keys.is_empty() && self.rate_limited.is_empty() && !remembered && !logged {
+            return;
+        }
+        let (handlers, catch_all) = (&self.handlers, &self.catch_all);
+        let is_registered = |id: &HandlerId| handlers.iter().chain(catch_all).any(|entry| entry.id == *id);
+        self.validators.retain(|id, _| is_registered(id));
+        self.keys.retain(|id, _| is_registered(id));
+        self.rate_limited.retain(|id, _| is_registered(id));
+        if let Some(remembered) = self.remembered.as_mut() {
+            remembered.retain(|id, _| is_registered(id));
+        }
+        if let Some(log) = self.invocation_log.as_mut() {
+            log.times.retain(|id, _| is_registered(id));
+        }
+    }
+
+    /// Sets a validator that decides, from its arguments, whether the handler with this id runs.
+    ///
+    /// During dispatch, after the params passed the arity checks, a handler whose validator returns `false` is
+    /// skipped rather than invoked; `vetoed` lists the skipped handlers afterwards. A `CatchAll` handler's validator
+    /// is asked once per param. Setting a validator replaces the previous one. Fails with
+    /// `CallbackError::UnknownHandlers` if no live handler has this id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert!(x >= 0)))).unwrap();
+    /// cb_manager.set_validator(HandlerId(0), |args| args[0] >= 0).unwrap();
+    ///
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(-1)]).unwrap();
+    /// assert_eq!(cb_manager.vetoed(), [HandlerId(0)]);
+    /// ```
+    pub fn set_validator(&mut self, id: HandlerId, validator: impl FnMut(&[T]) -> bool + Send + 'a) -> Result<(), CallbackError> {
+        let is_live = self.handlers.iter().chain(&self.catch_all).any(|entry| entry.id == id && entry.handler.strong_count() > 0);
+        if !is_live {
+            return Err(CallbackError::UnknownHandlers { ids: vec![id] });
+        }
+        self.validators.insert(id, Mutex::new(Box::new(validator)));
+        Ok(())
+    }
+
+    /// Returns the ids of the handlers skipped by their validator during the last dispatch, see `set_validator`.
+    pub fn vetoed(&self) -> &[HandlerId] {
+        &self.vetoed
+    }
+
+    /// Checks that the params match the active handlers. With `skip_unlockable`, handlers that can't be locked
+    /// are left out of the check, for the dispatch to report their errors and carry on without them.
+    fn try_match_params(&self, params: &[CallbackParams<T>], policy: ParamCountPolicy, skip_unlockable: bool) -> Result<(), CallbackError> {
+        let active_count = self.active_count();
+        let count_matches = match policy {
+            ParamCountPolicy::Exact => params.len() == active_count,
+            ParamCountPolicy::AtLeast => params.len() >= active_count,
+            ParamCountPolicy::AtMost => params.len() <= active_count,
+        };
+        if !count_matches {
+            return Err(CallbackError::ParamCountMismatch { expected: active_count, got: params.len() });
+        }
+
+        let arity_mismatch = |index, entry, param| match Self::arity_mismatch(index, entry, param) {
+            Err(_) if skip_unlockable => Ok(None),
+            result => result,
+        };
+        // Count first so the happy path doesn't build a mismatch list.
+        let mut mismatch_count = 0;
+        for (index, (entry, param)) in self.handlers.iter().zip(params).enumerate() {
+            if arity_mismatch(index, entry, param)?.is_some() {
+                mismatch_count += 1;
+            }
+        }
+        if mismatch_count == 0 {
+            return Ok(());
+        }
+
+        let mut mismatches = Vec::with_capacity(mismatch_count);
+        for (index, (entry, param)) in self.handlers.iter().zip(params).enumerate() {
+            mismatches.extend(arity_mismatch(index, entry, param)?);
+        }
+        Err(CallbackError::ParamTypeMismatch { mismatches })
+    }
+
+    fn arity_mismatch(index: usize, entry: &HandlerEntry<'a, T>, param: &CallbackParams<T>) -> Result<Option<ArityMismatch>, CallbackError> {
+        let mutex_handler = entry.handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+        let guard_handler = sync::lock(&mutex_handler)?;
+        if guard_handler.accepts(param.arity()) {
+            return Ok(None);
+        }
+        Ok(Some(ArityMismatch { index, id: entry.id, expected: guard_handler.arity(), got: param.arity() }))
+    }
+
+    /// Sets a key function used to order handlers before each `run_all`, lowest key first.
+    ///
+    /// The sort is stable, so handlers with equal keys keep their registration order. Handlers whose mutex is
+    /// poisoned are ordered last.
+    ///
+    /// Params are paired with handlers positionally in the sorted order, so a param vector has to be built
+    /// against that order rather than the registration order. This is easiest to get right when the key is
+    /// derived from the handler id or when all handlers share an arity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// // Run the highest arity handlers first.
+    /// cb_manager.set_order_by(|info| -(info.arity as i64));
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).is_ok());
+    /// ```
+    pub fn set_order_by(&mut self, key_fn: impl Fn(&HandlerInfo) -> i64 + Send + 'static) {
+        self.order_by = Some(Box::new(key_fn));
+    }
+
+    /// Returns the ids of the live handlers in the order `run_all` would invoke them, without dispatching.
+    ///
+    /// This is registration order, or the order given by `set_order_by` if set. Params are paired with the
+    /// positional handlers in this order. `CatchAll` handlers come last, in registration order, as they run after
+    /// the positional ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// cb_manager.set_order_by(|info| -(info.arity as i64));
+    ///
+    /// assert_eq!(cb_manager.dispatch_order(), vec![HandlerId(1), HandlerId(0)]);
+    /// ```
+    pub fn dispatch_order(&self) -> Vec<HandlerId> {
+        let mut order = self.positional_order();
+        order.extend(self.catch_all.iter().filter(|entry| entry.handler.strong_count() > 0).map(|entry| entry.id));
+        order
+    }
+
+    /// Returns the ids of the live positional handlers in dispatch order.
+    fn positional_order(&self) -> Vec<HandlerId> {
+        let mut order = self.handlers.iter().filter(|entry| entry.handler.strong_count() > 0).map(|entry| {
+            let key = self.order_by.as_ref().map(
+                |key_fn| Self::live_info(entry).map_or(i64::MAX, |(_, info)| key_fn(&info))
+            );
+            (key, entry.id)
+        }).collect::<Vec<(Option<i64>, HandlerId)>>();
+        order.sort_by_key(|(key, _)| *key);
+        order.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn apply_order(&mut self) {
+        if let Some(key_fn) = self.order_by.as_ref() {
+            self.handlers.sort_by_cached_key(
+                |entry| Self::live_info(entry).map_or(i64::MAX, |(_, info)| key_fn(&info))
+            );
+        }
+    }
+
+    /// Records the id of every handler invoked from now on, returning the shared buffer they are appended to.
+    ///
+    /// Meant for asserting dispatch order in tests. Calling it again replaces the previous buffer.
+    /// Available with the `test-util` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// let order = cb_manager.record_order();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()]).unwrap();
+    ///
+    /// assert_eq!(*order.lock().unwrap(), vec![HandlerId(0), HandlerId(1)]);
+    /// ```
+    #[cfg(feature = "test-util")]
+    pub fn record_order(&mut self) -> Arc<std::sync::Mutex<Vec<HandlerId>>> {
+        let recorder = Arc::new(std::sync::Mutex::new(vec![]));
+        self.order_recorder = Some(Arc::clone(&recorder));
+        recorder
+    }
+
+    /// Runs all active callback handlers, moving each param's arguments into the handler it is paired with.
+    ///
+    /// Unlike `run_all`, this doesn't require `Copy` arguments: each param goes to exactly one handler, so owned
+    /// values such as `String`s are moved rather than copied. Like `run_all`, it requires exactly one param per
+    /// active handler, of matching arity. Tracing, chained managers and replaying are not applied, since they need
+    /// to copy the params.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<String>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|s: String| assert_eq!(s, "owned")))).unwrap();
+    ///
+    /// assert!(cb_manager.run_all_consume(vec![CallbackParams::CallParams1("owned".to_string())]).is_ok());
+    /// ```
+    pub fn run_all_consume(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+        self.try_match_params(&params, ParamCountPolicy::Exact, false)?;
+
+        for ((index, entry), param) in self.handlers.iter().enumerate().zip(params) {
+            let mutex_handler = entry.handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+            let mut guard_handler = sync::lock(&mutex_handler)?;
+            let (expected, got) = (guard_handler.arity(), param.arity());
+            if !guard_handler.call_owned(param) {
+                let mismatch = ArityMismatch { index, id: entry.id, expected, got };
+                return Err(CallbackError::ParamTypeMismatch { mismatches: vec![mismatch] });
+            }
+            #[cfg(feature = "test-util")]
+            if let Some(recorder) = self.order_recorder.as_ref() {
+                recorder.lock().unwrap_or_else(std::sync::PoisonError::into_inner).push(entry.id);
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+impl<'a, T: Copy + 'a> CallbackManager<'a, T> {
+    /// Adds a new callback handler that is invoked at most `max_per_sec` times per second.
+    ///
+    /// The limit is enforced by a token bucket holding up to `max_per_sec` invocations, so a burst of that size
+    /// goes through at once. Invocations beyond the limit are skipped without an error; `rate_limited_drops` counts
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `CallbackError::Frozen` if the manager is frozen, see `freeze`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add_rate_limited(2, CallbackHandler::Callback1(Box::new(|x| println!("{x}")))).unwrap();
+    ///
+    /// for x in 0..3 {
+    ///     cb_manager.run_all(vec![CallbackParams::CallParams1(x)]).unwrap();
+    /// }
+    /// assert_eq!(cb_manager.rate_limited_drops(HandlerId(0)), Some(1));
+    /// ```
+    pub fn add_rate_limited(&mut self, max_per_sec: u32, handler: CallbackHandler<'a, T>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let handler = self.add(handler.rate_limited(max_per_sec, Arc::clone(&dropped)))?;
+        self.rate_limited.insert(HandlerId(self.next_id - 1), dropped);
+        Ok(handler)
+    }
+
+    /// Adds a handler of the given arity that sends its arguments to `tx` as a `Vec`, one message per invocation.
+    ///
+    /// Once the receiver is dropped the handler does nothing. Fails with `CallbackError::InvalidArity` if `arity`
+    /// is greater than 12.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use callback_manager::{CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let _handler = cb_manager.add_forwarding(2, tx).unwrap();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).unwrap();
+    ///
+    /// assert_eq!(rx.recv(), Ok(vec![1, 2]));
+    /// ```
+    pub fn add_forwarding(&mut self, arity: usize, tx: Sender<Vec<T>>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> where T: Send {
+        let handler = CallbackHandler::from_slice_fn(arity, move |args: &[T]| {
+            // A closed channel means nobody is listening anymore.
+            let _ = tx.send(args.to_vec());
+        });
+        self.add(handler.ok_or(CallbackError::InvalidArity { arity })?)
+    }
+
+    /// Returns how many invocations of the handler with this id were skipped for exceeding its rate limit, or
+    /// `None` if it wasn't added with `add_rate_limited` or is no longer registered.
+    pub fn rate_limited_drops(&self, id: HandlerId) -> Option<usize> {
+        self.rate_limited.get(&id).map(|dropped| dropped.load(Ordering::Relaxed))
+    }
+
+    /// Runs all active callback handlers with specific parameter lists.
+    ///
+    /// Malformed params, whether empty, too many or of the wrong arities, are reported as a `CallbackError` rather
+    /// than a panic. Only a panicking callback unwinds out of `run_all`.
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// let mut sum = 0;
+    /// let sum_pointer = &sum as *const i32;
+    /// 
+    /// let mut cb_manager = callback_manager::CallbackManager::<i32>::new();
+    /// 
+    /// let _handler0 = cb_manager.add(callback_manager::CallbackHandler::Callback1(Box::new(|x: i32| {sum += x;}))).unwrap();
+    /// 
+    /// cb_manager.run_all(vec![
+    ///     callback_manager::CallbackParams::CallParams1(100),
+    /// ]);
+    /// 
+    /// unsafe {assert_eq!(*sum_pointer, 100);}
+    /// ```
+    pub fn run_all(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        self.dispatch(params, &mut DispatchState::default())
+    }
+
+    /// Runs all active callback handlers like `run_all`, but invokes every handler it can instead of failing, and
+    /// returns the failures with their index in dispatch order.
+    ///
+    /// Params aren't checked up front: each handler is invoked with the param at its position unless they don't
+    /// match or its mutex is poisoned, which is recorded and skipped. If the counts differ, the handlers or params
+    /// left unpaired are recorded as one `CallbackError::ParamCountMismatch` at the first unpaired index.
+    /// `CatchAll` handlers come after the positional ones, as listed by `dispatch_order`. Chained managers aren't
+    /// forwarded to, and failures go to the returned list rather than to the error sink.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 1)))).unwrap();
+    ///
+    /// let failures = cb_manager.run_all_lenient(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(1)]);
+    /// assert_eq!(failures.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![0]);
+    /// ```
+    pub fn run_all_lenient(&mut self, params: Vec<CallbackParams<T>>) -> Vec<(usize, CallbackError)> {
+        let mut state = DispatchState { errors: Some(vec![]), lenient: true, ..DispatchState::default() };
+        // Errors are collected in `state`, so the dispatch itself doesn't fail.
+        let _ = self.dispatch(params, &mut state);
+        state.errors.unwrap_or_default()
+    }
+
+    /// Runs `run_all` for every params batch already waiting in `rx`, without blocking, and returns the number of
+    /// handler invocations, counted like `run_stream`.
+    ///
+    /// Stops at the first batch that fails, returning its error; the batches after it stay in the channel. A
+    /// disconnected channel is drained like an open one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// tx.send(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).unwrap();
+    /// tx.send(vec![CallbackParams::CallParams1(2), CallbackParams::CallParams0()]).unwrap();
+    /// drop(tx);
+    ///
+    /// assert_eq!(cb_manager.drive_from(&rx), Ok(4));
+    /// assert_eq!(cb_manager.drive_from(&rx), Ok(0));
+    /// ```
+    pub fn drive_from(&mut self, rx: &Receiver<Vec<CallbackParams<T>>>) -> Result<usize, CallbackError> {
+        self.run_stream(rx.try_iter())
+    }
+
+    /// Runs `run_all` for each params batch from `batches`, pulling the next batch only once the previous one was
+    /// dispatched, and returns the number of handler invocations.
+    ///
+    /// Only handlers actually called are counted: a `CatchAll` handler counts once per param, while handlers
+    /// vetoed by their validator or skipped as duplicates don't count. Stops at the first batch that fails,
+    /// returning its error without pulling further batches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let batches = (0..1000).map(|x| vec![CallbackParams::CallParams1(x)]);
+    /// assert_eq!(cb_manager.run_stream(batches), Ok(1000));
+    /// ```
+    pub fn run_stream<I: Iterator<Item = Vec<CallbackParams<T>>>>(&mut self, batches: I) -> Result<usize, CallbackError> {
+        let mut invocations = 0;
+        for params in batches {
+            let mut state = DispatchState::default();
+            self.dispatch(params, &mut state)?;
+            invocations += state.invocations;
+        }
+        Ok(invocations)
+    }
+
+    /// Runs all active callback handlers like `run_all`, passing a handler only the leading arguments it accepts
+    /// when its param has more.
+    ///
+    /// Only surplus arguments are tolerated: a `CallParams3` paired with a `Callback2` handler invokes it with the
+    /// first two arguments, but a param with fewer arguments than its handler takes is still a
+    /// `CallbackError::ParamTypeMismatch`. Traces record the truncated params.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback2(Box::new(|x, y| assert_eq!((x, y), (1, 2))))).unwrap();
+    ///
+    /// assert!(cb_manager.run_all_truncate(vec![CallbackParams::CallParams3(1, 2, 3)]).is_ok());
+    /// assert!(cb_manager.run_all_truncate(vec![CallbackParams::CallParams1(1)]).is_err());
+    /// ```
+    pub fn run_all_truncate(&mut self, mut params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+        for (entry, param) in self.handlers.iter().zip(params.iter_mut()) {
+            let Some(mutex_handler) = entry.handler.upgrade() else {
+                continue;
+            };
+            let guard_handler = sync::lock_ignoring_poison(&mutex_handler);
+            if guard_handler.accepts(param.arity()) {
+                continue;
+            }
+            if let Some(arity) = guard_handler.accepted_prefix(param.arity()) {
+                *param = param.with_slice(|args| CallbackParams::try_from(&args[..arity]))
+                    .expect("a prefix of params has at most 12 arguments");
+            }
+        }
+        self.run_all(params)
+    }
+
+    /// Runs clones of all active callback handlers like `run_all`, leaving the registered handlers and their state
+    /// untouched.
+    ///
+    /// Every active handler must be a `CallbackHandler::Cloneable`, otherwise this fails with
+    /// `CallbackError::NotCloneable` listing the other handlers, `CatchAll` ones included, and nothing runs. The
+    /// clones are discarded afterwards. Like `run_all_unchecked`, this doesn't trace, notify the observer or forward
+    /// to chained managers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, CloneableCallback};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let seen = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let mut total = 0;
+    /// let seen_clone = Arc::clone(&seen);
+    /// let _handler = cb_manager.add(CallbackHandler::Cloneable(CloneableCallback::new(1, move |args: &[i32]| {
+    ///     total += args[0];
+    ///     seen_clone.lock().unwrap().push(total);
+    /// }).unwrap())).unwrap();
+    ///
+    /// cb_manager.run_all_scoped(vec![CallbackParams::CallParams1(5)]).unwrap();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+    ///
+    /// assert_eq!(*seen.lock().unwrap(), vec![5, 1]);
+    /// ```
+    pub fn run_all_scoped(&mut self, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+        self.try_match_params(&params, ParamCountPolicy::Exact, false)?;
+
+        let mut clones = Vec::with_capacity(self.handlers.len());
+        let mut not_cloneable = vec![];
+        for entry in self.handlers.iter().chain(&self.catch_all) {
+            let mutex_handler = entry.handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+            let guard_handler = sync::lock(&mutex_handler)?;
+            match &*guard_handler {
+                CallbackHandler::Cloneable(cloneable) => {
+                    clones.push((entry.id, CallbackHandler::Cloneable(cloneable.clone())));
+                },
+                _ => not_cloneable.push(entry.id),
+            }
+        }
+        if !not_cloneable.is_empty() {
+            return Err(CallbackError::NotCloneable { ids: not_cloneable });
+        }
+
+        for (index, ((id, mut handler), param)) in clones.into_iter().zip(&params).enumerate() {
+            if !handler.call(param) {
+                let mismatch = ArityMismatch { index, id, expected: handler.arity(), got: param.arity() };
+                return Err(CallbackError::ParamTypeMismatch { mismatches: vec![mismatch] });
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs all active callback handlers with params that are already known to match, skipping the upfront
+    /// validation `run_all` does.
+    ///
+    /// Params are still paired with handlers positionally. Surplus params or handlers are ignored, and a param
+    /// whose arity doesn't match its handler stops the dispatch with `CallbackError::ParamTypeMismatch` after the
+    /// handlers before it have already run. Unlike `run_all`, this doesn't record traces, notify the observer,
+    /// remember params for replay or forward to chained managers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let params = [CallbackParams::CallParams1(1)];
+    /// for _ in 0..3 {
+    ///     cb_manager.run_all_unchecked(&params).unwrap();
+    /// }
+    /// ```
+    pub fn run_all_unchecked(&mut self, params: &[CallbackParams<T>]) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+        self.invoke_entries(self.handlers.iter().zip(params), &mut DispatchState::default())
+    }
+
+    /// Runs all active callback handlers like `run_all`, calling `gen` with each handler's id and dispatch index to
+    /// build its params.
+    ///
+    /// Each generated param must match the arity of its handler, otherwise `CallbackError::ParamTypeMismatch`
+    /// lists the handlers whose params didn't match and no handler is invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<usize>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 0)))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 10)))).unwrap();
+    ///
+    /// assert!(cb_manager.run_all_gen(|_id, index| CallbackParams::CallParams1(index * 10)).is_ok());
+    /// ```
+    pub fn run_all_gen(&mut self, mut gen: impl FnMut(HandlerId, usize) -> CallbackParams<T>) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+        let params = self.handlers.iter().enumerate().map(|(index, entry)| gen(entry.id, index)).collect();
+        self.run_all(params)
+    }
+
+    /// Runs all active callback handlers like `run_all`, cycling through `params` when there are fewer params than
+    /// active handlers: the handler at dispatch index `i` gets `params[i % params.len()]`.
+    ///
+    /// Each cycled param must match the arity of its handler, otherwise `CallbackError::ParamTypeMismatch` lists
+    /// the handlers whose params didn't match and no handler is invoked. With as many params as active handlers or
+    /// more, or with no params, this is the same as `run_all`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 1)))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 2)))).unwrap();
+    /// let _handler2 = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 1)))).unwrap();
+    ///
+    /// let params = [CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)];
+    /// assert!(cb_manager.run_all_cycle(&params).is_ok());
+    /// ```
+    pub fn run_all_cycle(&mut self, params: &[CallbackParams<T>]) -> Result<(), CallbackError> {
+        if params.is_empty() || params.len() >= self.active_count() {
+            return self.run_all(params.to_vec());
+        }
+        self.run_all_gen(|_id, index| params[index % params.len()])
+    }
+
+    /// Invokes every active handler once with `T::default()` for each of its arguments, and returns the number of
+    /// handlers invoked.
+    ///
+    /// Meant for signals like a shutdown, where the argument values don't matter, so it requires `T: Default`.
+    /// Handlers are invoked in dispatch order, whatever their arity: a `Multi` handler gets its largest arity and
+    /// a `CatchAll` handler gets no arguments. Validators are ignored, and nothing is traced, observed or
+    /// forwarded to chained managers. Stops at the first handler whose mutex is poisoned, returning
+    /// `CallbackError::LockPoisoned`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback2(Box::new(|x, y| assert_eq!((x, y), (0, 0))))).unwrap();
+    ///
+    /// assert_eq!(cb_manager.ping_all(), Ok(2));
+    /// ```
+    pub fn ping_all(&mut self) -> Result<usize, CallbackError> where T: Default {
+        self.drop_inactive();
+        self.apply_order();
+        let mut invoked = 0;
+        for entry in self.handlers.iter().chain(&self.catch_all) {
+            let Some(mutex_handler) = entry.handler.upgrade() else {
+                continue;
+            };
+            let mut guard_handler = sync::lock(&mutex_handler)?;
+            let params = CallbackParams::try_from(vec![T::default(); guard_handler.arity()])?;
+            guard_handler.call(&params);
+            invoked += 1;
+        }
+        Ok(invoked)
+    }
+
+    /// Runs all active callback handlers like `run_all`, accepting a param count allowed by `policy`.
+    ///
+    /// Under `ParamCountPolicy::AtLeast` params past the last active handler are ignored, and under
+    /// `ParamCountPolicy::AtMost` active handlers past the last param are skipped. Chained managers are run with
+    /// the same policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, ParamCountPolicy};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let params = vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)];
+    /// assert!(cb_manager.run_all_with_policy(params.clone(), ParamCountPolicy::Exact).is_err());
+    /// assert!(cb_manager.run_all_with_policy(params, ParamCountPolicy::AtLeast).is_ok());
+    /// ```
+    pub fn run_all_with_policy(&mut self, params: Vec<CallbackParams<T>>, policy: ParamCountPolicy) -> Result<(), CallbackError> {
+        self.dispatch(params, &mut DispatchState { policy, ..DispatchState::default() })
+    }
+
+    /// Runs all active callback handlers like `run_all`, returning the ids of the handlers whose invocation took
+    /// longer than `budget`.
+    ///
+    /// This only detects overruns after the fact: a running callback is never interrupted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| std::thread::sleep(Duration::from_millis(20))))).unwrap();
+    ///
+    /// let params = vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()];
+    /// assert_eq!(cb_manager.run_all_budget(params, Duration::from_millis(10)), Ok(vec![HandlerId(1)]));
+    /// ```
+    pub fn run_all_budget(&mut self, params: Vec<CallbackParams<T>>, budget: Duration) -> Result<Vec<HandlerId>, CallbackError> {
+        let mut state = DispatchState { budget: Some(budget), ..DispatchState::default() };
+        self.dispatch(params, &mut state)?;
+        Ok(state.over_budget)
+    }
+
+    /// Runs all active callback handlers like `run_all` until `deadline`, returning the ids of the handlers skipped
+    /// because it had passed.
+    ///
+    /// The params are validated up front as for `run_all`. The deadline is checked before each invocation, so a
+    /// callback that is already running is never interrupted and may overrun it. Chained managers are run in full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| std::thread::sleep(Duration::from_millis(20))))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    ///
+    /// let params = vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()];
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// assert_eq!(cb_manager.run_all_deadline(params, deadline), Ok(vec![HandlerId(1)]));
+    /// ```
+    pub fn run_all_deadline(&mut self, params: Vec<CallbackParams<T>>, deadline: Instant) -> Result<Vec<HandlerId>, CallbackError> {
+        let mut state = DispatchState { deadline: Some(deadline), ..DispatchState::default() };
+        self.dispatch(params, &mut state)?;
+        Ok(state.skipped)
+    }
+
+    /// Runs all active callback handlers like `run_all`, invoking the handler with id `first` before the others for
+    /// this dispatch only.
+    ///
+    /// Params are paired positionally with the boosted order: the first param goes to `first`, and the rest to the
+    /// other handlers in their usual order, as listed by `dispatch_order`. The order is restored afterwards.
+    /// Chained managers get the params unchanged. Fails with `CallbackError::UnknownHandlers` if no live handler
+    /// has this id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let params = vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()];
+    /// assert!(cb_manager.run_all_prioritizing(HandlerId(1), params).is_ok());
+    /// assert_eq!(cb_manager.dispatch_order(), vec![HandlerId(0), HandlerId(1)]);
+    /// ```
+    pub fn run_all_prioritizing(&mut self, first: HandlerId, params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        if !self.handlers.iter().any(|entry| entry.id == first && entry.handler.strong_count() > 0) {
+            return Err(CallbackError::UnknownHandlers { ids: vec![first] });
+        }
+        self.dispatch(params, &mut DispatchState { first: Some(first), ..DispatchState::default() })
+    }
+
+    fn dispatch(&mut self, params: Vec<CallbackParams<T>>, state: &mut DispatchState) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+
+        if self.trace.is_some() || self.observer.is_some() || self.remembered.is_some() || self.invocation_log.is_some() {
+            state.invoked = Some(vec![]);
+        }
+        if self.error_sink.is_some() && state.errors.is_none() {
+            state.errors = Some(vec![]);
+        }
+        if self.dedupe {
+            state.seen = Some(HashSet::new());
+        }
+        let first = state.first.and_then(|id| self.handlers.iter().position(|entry| entry.id == id));
+        let (result, ids) = {
+            let prioritized = Prioritized::new(self, first);
+            let manager = &mut *prioritized.manager;
+            let mut result = manager.invoke_all(&params, state);
+            if result.is_ok() && !state.lenient {
+                result = manager.forward_to_chained(&params, state);
+            }
+            if result.is_ok() && manager.replay.is_some() {
+                manager.last_params.clone_from(&params);
+            }
+            let ids = state.invoked.take();
+            if let (Some(remembered), Some(ids)) = (manager.remembered.as_mut(), ids.as_ref()) {
+                for (entry, param) in manager.handlers.iter().zip(&params) {
+                    if ids.contains(&entry.id) {
+                        remembered.insert(entry.id, *param);
+                    }
+                }
+            }
+            (result, ids)
+        };
+        if let (Some(log), Some(ids)) = (self.invocation_log.as_mut().filter(|log| log.capacity > 0), ids.as_ref()) {
+            let now = Instant::now();
+            for id in ids {
+                let times = log.times.entry(*id).or_default();
+                if times.len() == log.capacity {
+                    times.pop_front();
+                }
+                times.push_back(now);
+            }
+        }
+        if let Some(observer) = &self.observer {
+            let event = DispatchEvent {
+                timestamp: Instant::now(),
+                ids_invoked: ids.clone().unwrap_or_default(),
+                error: result.as_ref().err().cloned(),
+            };
+            // A closed receiver just means nobody is listening anymore.
+            let _ = observer.send(event);
+        }
+        if let (Some(trace), Some(ids)) = (self.trace.as_mut(), ids) {
+            trace.push(TraceEntry { params, ids });
+        }
+        self.vetoed = std::mem::take(&mut state.vetoed);
+        if let Some(sink) = self.error_sink.as_mut().filter(|_| !state.lenient) {
+            state.errors.take().unwrap_or_default().into_iter().map(|(_, error)| error).chain(result.err()).for_each(sink);
+            return Ok(());
+        }
+        result
+    }
+
+    /// Chains another manager so that every successful `run_all` on this manager forwards the same params to it.
+    ///
+    /// Chained managers forward to their own chained managers in turn, forming a dispatch graph. Each manager of
+    /// the graph dispatches an event at most once, so cycles don't recurse forever. A chained manager that another
+    /// thread holds locked is waited for. Chained managers that have been dropped are removed.
+    ///
+    /// `other` is locked briefly to read its id, so the caller must not hold its lock. A manager that has already
+    /// been dropped is not chained.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, Mutex};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let mut child_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    /// let _child_handler = child_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let child_manager = Arc::new(Mutex::new(child_manager));
+    /// cb_manager.chain(Arc::downgrade(&child_manager));
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).is_ok());
+    /// ```
+    pub fn chain(&mut self, other: Weak<Mutex<CallbackManager<'a, T>>>) {
+        if let Some(mutex_manager) = other.upgrade() {
+            let id = sync::lock_ignoring_poison(&mutex_manager).manager_id;
+            self.chained.push((id, other));
+        }
+    }
+
+    fn forward_to_chained(&mut self, params: &[CallbackParams<T>], state: &mut DispatchState) -> Result<(), CallbackError> {
+        self.chained.retain(|(_, manager)| manager.strong_count() > 0);
+        // A lone manager doesn't need to record itself, which spares the allocation.
+        if self.chained.is_empty() && state.visited.is_empty() {
+            return Ok(());
+        }
+        state.visited.insert(self.manager_id);
+        for (id, weak_manager) in self.chained.iter() {
+            // Locking a manager already dispatching up the chain would deadlock, so it is recognized by its id.
+            if state.visited.contains(id) {
+                continue;
+            }
+            let Some(mutex_manager) = weak_manager.upgrade() else {
+                continue;
+            };
+            let mut manager = sync::lock(&mutex_manager)?;
+            let mut chained_state = DispatchState {
+                policy: state.policy,
+                visited: std::mem::take(&mut state.visited),
+                ..DispatchState::default()
+            };
+            let result = manager.dispatch(params.to_vec(), &mut chained_state);
+            state.visited = chained_state.visited;
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Runs all active callback handlers with params grouped by arity.
+    ///
+    /// Each bucket holds the params for the handlers of its arity, in the order those handlers are dispatched.
+    /// This way the params don't have to be interleaved to match the overall handler order. Every bucket must
+    /// hold exactly one param per live handler of its arity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap();
+    /// let _handler2 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let buckets = HashMap::from([
+    ///     (1, vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]),
+    ///     (2, vec![CallbackParams::CallParams2(3, 4)]),
+    /// ]);
+    ///
+    /// assert!(cb_manager.run_all_bucketed(buckets).is_ok());
+    /// ```
+    pub fn run_all_bucketed(&mut self, buckets: HashMap<usize, Vec<CallbackParams<T>>>) -> Result<(), CallbackError> {
+        self.drop_inactive();
+        self.apply_order();
+
+        let mut arities = vec![];
+        let mut counts = HashMap::new();
+        for entry in self.handlers.iter() {
+            let (_, info) = Self::live_info(entry).ok_or(CallbackError::LockPoisoned)?;
+            arities.push(info.arity);
+            *counts.entry(info.arity).or_insert(0) += 1;
+        }
+
+        let mut bucket_arities = counts.keys().chain(buckets.keys()).copied().collect::<Vec<usize>>();
+        bucket_arities.sort_unstable();
+        bucket_arities.dedup();
+        for arity in bucket_arities {
+            let expected = counts.get(&arity).copied().unwrap_or(0);
+            let got = buckets.get(&arity).map_or(0, Vec::len);
+            if expected != got {
+                return Err(CallbackError::BucketSizeMismatch { arity, expected, got });
+            }
+        }
+
+        let mut buckets = buckets.into_iter().map(|(arity, bucket)| (arity, bucket.into_iter())).collect::<HashMap<_, _>>();
+        let params = arities.iter().filter_map(
+            |arity| buckets.get_mut(arity).and_then(Iterator::next)
+        ).collect();
+        self.run_all(params)
+    }
+
+    /// Runs all active callback handlers with `map` applied to every argument of the params.
+    ///
+    /// The params themselves are left untouched, which makes this a lightweight interception point for unit
+    /// conversion or clamping. Mapping keeps the arity of each param.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|x| assert_eq!(x, 10)))).unwrap();
+    ///
+    /// let params = vec![CallbackParams::CallParams1(20)];
+    /// assert!(cb_manager.run_all_mapped(&params, |x| x.min(10)).is_ok());
+    /// ```
+    pub fn run_all_mapped(&mut self, params: &[CallbackParams<T>], map: impl Fn(T) -> T) -> Result<(), CallbackError> {
+        self.run_all(params.iter().map(|param| param.map(&map)).collect())
+    }
+
+    /// Runs only the handlers with the given ids, in the order of `ids`, pairing each with the param at the same
+    /// position.
+    ///
+    /// Fails without invoking anything if an id is unknown or its handler was dropped, or if a param doesn't
+    /// match the arity of its handler. A `CatchAll` handler accepts a param of any arity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, CallbackError, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// assert!(cb_manager.run_some(&[HandlerId(1)], vec![CallbackParams::CallParams1(1)]).is_ok());
+    /// assert_eq!(
+    ///     cb_manager.run_some(&[HandlerId(5)], vec![CallbackParams::CallParams0()]),
+    ///     Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(5)] })
+    /// );
+    /// ```
+    pub fn run_some(&mut self, ids: &[HandlerId], params: Vec<CallbackParams<T>>) -> Result<(), CallbackError> {
+        if params.len() != ids.len() {
+            return Err(CallbackError::ParamCountMismatch { expected: ids.len(), got: params.len() });
+        }
+
+        let mut entries = Vec::with_capacity(ids.len());
+        let mut unknown = vec![];
+        for id in ids {
+            match self.handlers.iter().chain(&self.catch_all).find(|entry| entry.id == *id && entry.handler.strong_count() > 0) {
+                Some(entry) => entries.push(entry),
+                None => unknown.push(*id),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(CallbackError::UnknownHandlers { ids: unknown });
+        }
+
+        let mut mismatches = vec![];
+        for (index, (entry, param)) in entries.iter().zip(&params).enumerate() {
+            mismatches.extend(Self::arity_mismatch(index, entry, param)?);
+        }
+        if !mismatches.is_empty() {
+            return Err(CallbackError::ParamTypeMismatch { mismatches });
+        }
+
+        self.invoke_entries(entries.into_iter().zip(&params), &mut DispatchState::default())
+    }
+
+    /// Runs only the handler with this id, with `params`, like `run_some` with a single id.
+    ///
+    /// Fails with `CallbackError::UnknownHandlers` if the id is unknown or its handler was dropped, or with
+    /// `CallbackError::ParamTypeMismatch` if the params don't match the handler's arity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let (id, _handler1) = cb_manager.add_with_id(CallbackHandler::Callback2(Box::new(|x, y| assert_eq!(x + y, 3)))).unwrap();
+    ///
+    /// assert!(cb_manager.run_one(id, CallbackParams::CallParams2(1, 2)).is_ok());
+    /// assert!(cb_manager.run_one(id, CallbackParams::CallParams0()).is_err());
+    /// ```
+    pub fn run_one(&mut self, id: HandlerId, params: CallbackParams<T>) -> Result<(), CallbackError> {
+        self.run_some(&[id], vec![params])
+    }
+
+    fn invoke_all(&self, params: &[CallbackParams<T>], state: &mut DispatchState) -> Result<(), CallbackError> {
+        if !state.lenient {
+            self.try_match_params(params, state.policy, state.errors.is_some())?;
+        }
+        self.invoke_entries(self.handlers.iter().zip(params), state)?;
+        if state.lenient && params.len() != self.handlers.len() {
+            let error = CallbackError::ParamCountMismatch { expected: self.handlers.len(), got: params.len() };
+            state.recover(params.len().min(self.handlers.len()), error)?;
+        }
+        self.invoke_catch_all(params, state)
+    }
+
+    fn invoke_catch_all(&self, params: &[CallbackParams<T>], state: &mut DispatchState) -> Result<(), CallbackError> {
+        for (index, entry) in self.catch_all.iter().enumerate() {
+            let Some(mutex_handler) = entry.handler.upgrade() else {
+                continue;
+            };
+            if state.is_past_deadline() {
+                state.skipped.push(entry.id);
+                continue;
+            }
+            if state.is_duplicate(&mutex_handler) {
+                continue;
+            }
+            if let Err(error) = self.invoke_catch_all_entry(entry, &mutex_handler, params, state) {
+                state.recover(self.handlers.len() + index, error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn invoke_catch_all_entry(
+        &self,
+        entry: &HandlerEntry<'a, T>,
+        mutex_handler: &Mutex<CallbackHandler<'a, T>>,
+        params: &[CallbackParams<T>],
+        state: &mut DispatchState,
+    ) -> Result<(), CallbackError> {
+        let mut guard_handler = sync::lock(mutex_handler)?;
+        for param in params {
+            if self.validate(entry.id, param)? {
+                guard_handler.call(param);
+                state.invocations += 1;
+            } else {
+                state.vetoed.push(entry.id);
+            }
+        }
+        if let Some(ids) = state.invoked.as_mut() {
+            ids.push(entry.id);
+        }
+        Ok(())
+    }
+
+    fn invoke_entries<'b>(
+        &self,
+        pairs: impl Iterator<Item = (&'b HandlerEntry<'a, T>, &'b CallbackParams<T>)>,
+        state: &mut DispatchState,
+    ) -> Result<(), CallbackError> where 'a: 'b {
+        for (index, (entry, param)) in pairs.enumerate() {
+            if state.is_past_deadline() {
+                state.skipped.push(entry.id);
+                continue;
+            }
+            if let Err(error) = self.invoke_entry(index, entry, param, state) {
+                state.recover(index, error)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn invoke_entry(&self, index: usize, entry: &HandlerEntry<'a, T>, param: &CallbackParams<T>, state: &mut DispatchState) -> Result<(), CallbackError> {
+        let mutex_handler = entry.handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+        if state.is_duplicate(&mutex_handler) {
+            return Ok(());
+        }
+        let mut guard_handler = sync::lock(&mutex_handler)?;
+        if !guard_handler.accepts(param.arity()) {
+            let mismatch = ArityMismatch { index, id: entry.id, expected: guard_handler.arity(), got: param.arity() };
+            return Err(CallbackError::ParamTypeMismatch { mismatches: vec![mismatch] });
+        }
+        if !self.validate(entry.id, param)? {
+            state.vetoed.push(entry.id);
+            return Ok(());
+        }
+        let started = state.budget.map(|_| Instant::now());
+        guard_handler.call(param);
+        state.invocations += 1;
+        if let (Some(budget), Some(started)) = (state.budget, started) {
+            if started.elapsed() > budget {
+                state.over_budget.push(entry.id);
+            }
+        }
+        if let Some(ids) = state.invoked.as_mut() {
+            ids.push(entry.id);
+        }
+        #[cfg(feature = "test-util")]
+        if let Some(recorder) = self.order_recorder.as_ref() {
+            recorder.lock().unwrap_or_else(std::sync::PoisonError::into_inner).push(entry.id);
+        }
+        Ok(())
+    }
+
+    fn validate(&self, id: HandlerId, param: &CallbackParams<T>) -> Result<bool, CallbackError> {
+        match self.validators.get(&id) {
+            Some(validator) => Ok(param.with_slice(&mut *sync::lock(validator)?)),
+            None => Ok(true),
+        }
+    }
+
+    /// Enables or disables replaying the last dispatched params to newly added handlers.
+    ///
+    /// When enabled, each successful `run_all` remembers its params, and `add` immediately invokes the new handler
+    /// with the first remembered param of matching arity. Handlers with no param of matching arity are not invoked.
+    /// Disabling forgets the remembered params.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let value = Arc::new(Mutex::new(0));
+    ///
+    /// cb_manager.set_replay_last(true);
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(42)]).unwrap();
+    ///
+    /// let value_clone = Arc::clone(&value);
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(move |x| *value_clone.lock().unwrap() = x))).unwrap();
+    ///
+    /// assert_eq!(*value.lock().unwrap(), 42);
+    /// ```
+    pub fn set_replay_last(&mut self, enabled: bool) {
+        if enabled {
+            self.replay = Some(Self::replay_to);
+        } else {
+            self.replay = None;
+            self.last_params.clear();
+        }
+    }
+
+    /// Starts recording when handlers are invoked, keeping the last `capacity` invocation times per handler, for
+    /// `count_invocations_since`.
+    ///
+    /// Invocations by `run_all` and the `run_all_*` methods built on it are recorded, `CatchAll` ones included. All
+    /// invocations of a dispatch are recorded with the time it finished invoking handlers. Calling it again changes
+    /// the capacity and forgets the recorded times.
+    pub fn track_invocations(&mut self, capacity: usize) {
+        self.invocation_log = Some(InvocationLog { capacity, times: HashMap::new() });
+    }
+
+    /// Returns how many times the handler with this id was invoked since `since`, counting at most the capacity
+    /// given to `track_invocations`.
+    ///
+    /// Returns `None` if no live handler has this id or invocations aren't tracked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Instant;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// cb_manager.track_invocations(100);
+    ///
+    /// cb_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+    /// let since = Instant::now();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+    ///
+    /// assert_eq!(cb_manager.count_invocations_since(HandlerId(0), since), Some(1));
+    /// assert_eq!(cb_manager.count_invocations_since(HandlerId(1), since), None);
+    /// ```
+    pub fn count_invocations_since(&self, id: HandlerId, since: Instant) -> Option<u64> {
+        let log = self.invocation_log.as_ref()?;
+        if !self.handlers.iter().chain(&self.catch_all).any(|entry| entry.id == id && entry.handler.strong_count() > 0) {
+            return None;
+        }
+        let times = log.times.get(&id).map_or(0, |times| times.iter().rev().take_while(|time| **time >= since).count());
+        Some(times as u64)
+    }
+
+    /// Enables or disables invoking each handler at most once per dispatch.
+    ///
+    /// The same handler can be registered under several ids, for instance by passing clones of one `Weak` to
+    /// `add_weak`. By default a dispatch invokes it once per registration. When enabled, `run_all` and the
+    /// `run_all_*` methods built on it only invoke it for its first registration in dispatch order. The later
+    /// registrations still count as active handlers and are still paired with a param, which is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, Mutex};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let handler = Arc::new(Mutex::new(CallbackHandler::Callback1(Box::new(|x| println!("{x}")))));
+    /// cb_manager.add_weak(Arc::downgrade(&handler)).unwrap();
+    /// cb_manager.add_weak(Arc::downgrade(&handler)).unwrap();
+    ///
+    /// cb_manager.set_dedupe_within_run(true);
+    /// // Prints 1 only.
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]).is_ok());
+    /// ```
+    pub fn set_dedupe_within_run(&mut self, enabled: bool) {
+        self.dedupe = enabled;
+    }
+
+    /// Enables or disables remembering the params each handler was last invoked with, for `refire`.
+    ///
+    /// When enabled, `run_all` and the `run_all_*` methods built on it remember the param of every positional
+    /// handler they invoke. Disabling forgets the remembered params.
+    pub fn set_remember_params(&mut self, enabled: bool) {
+        if !enabled {
+            self.remembered = None;
+        } else if self.remembered.is_none() {
+            self.remembered = Some(HashMap::new());
+        }
+    }
+
+    /// Invokes the handler with this id again with the params it was last invoked with, see `set_remember_params`.
+    ///
+    /// Fails with `CallbackError::UnknownHandlers` if no live handler has this id, or with
+    /// `CallbackError::NotInvoked` if it wasn't invoked since params started being remembered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let seen = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let seen_clone = Arc::clone(&seen);
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(move |x| seen_clone.lock().unwrap().push(x)))).unwrap();
+    ///
+    /// cb_manager.set_remember_params(true);
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(7)]).unwrap();
+    /// cb_manager.refire(HandlerId(0)).unwrap();
+    ///
+    /// assert_eq!(*seen.lock().unwrap(), vec![7, 7]);
+    /// ```
+    pub fn refire(&mut self, id: HandlerId) -> Result<(), CallbackError> {
+        if !self.handlers.iter().any(|entry| entry.id == id && entry.handler.strong_count() > 0) {
+            return Err(CallbackError::UnknownHandlers { ids: vec![id] });
+        }
+        let param = self.remembered.as_ref().and_then(|remembered| remembered.get(&id)).copied();
+        self.run_some(&[id], vec![param.ok_or(CallbackError::NotInvoked { id })?])
+    }
+
+    fn replay_to(last_params: &[CallbackParams<T>], handler: &mut CallbackHandler<'a, T>) {
+        if let Some(param) = last_params.iter().find(|param| handler.accepts(param.arity())) {
+            handler.call(param);
+        }
+    }
+
+    /// Sends a `DispatchEvent` to `tx` after every `run_all` call, whether it succeeded or not.
+    ///
+    /// This replaces any previous observer. Events are dropped silently once the receiver is gone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// cb_manager.set_observer(tx);
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+    ///
+    /// let event = rx.recv().unwrap();
+    /// assert_eq!(event.ids_invoked, vec![HandlerId(0)]);
+    /// assert_eq!(event.error, None);
+    /// ```
+    pub fn set_observer(&mut self, tx: Sender<DispatchEvent>) {
+        self.observer = Some(tx);
+    }
+
+    /// Routes dispatch errors to `sink` instead of returning them, so that `run_all` always returns `Ok`.
+    ///
+    /// This replaces any previous sink, and applies to `run_all` and the `run_all_*` methods built on it. Errors
+    /// of a single handler are recoverable: a `HandlerDropped`, a `LockPoisoned` handler or validator, or a
+    /// handler whose arity no longer matches its param is reported and skipped, and dispatch continues with the
+    /// next handler. Errors about the params as a whole are fatal: a `ParamCountMismatch` or `ParamTypeMismatch`
+    /// found before dispatching is reported and no handler is invoked, as when it's returned. The error of a
+    /// chained manager is reported after this manager's handlers ran. Observers still see the returned error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackError, CallbackHandler, CallbackManager};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let errors = Arc::new(Mutex::new(vec![]));
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let errors_clone = Arc::clone(&errors);
+    /// cb_manager.set_error_sink(move |error| errors_clone.lock().unwrap().push(error));
+    ///
+    /// assert!(cb_manager.run_all(vec![]).is_ok());
+    /// assert_eq!(*errors.lock().unwrap(), vec![CallbackError::ParamCountMismatch { expected: 1, got: 0 }]);
+    /// ```
+    pub fn set_error_sink(&mut self, sink: impl FnMut(CallbackError) + Send + 'a) {
+        self.error_sink = Some(Box::new(sink));
+    }
+
+    /// Starts recording every `run_all` call into a trace buffer.
+    ///
+    /// Tracing is off by default, in which case nothing is recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// cb_manager.enable_tracing();
+    /// cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+    ///
+    /// let trace = cb_manager.take_trace();
+    /// assert_eq!(trace[0].params, vec![CallbackParams::CallParams1(1)]);
+    /// assert_eq!(trace[0].ids, vec![HandlerId(0)]);
+    /// ```
+    pub fn enable_tracing(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(vec![]);
+        }
+    }
+
+    /// Returns the recorded trace and clears the buffer. Tracing stays enabled.
+    pub fn take_trace(&mut self) -> Vec<TraceEntry<T>> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Runs the params of each trace entry through `run_all`, in order.
+    ///
+    /// The trace is meant to be replayed against a manager holding equivalent handlers registered in the same order.
+    /// Replaying stops at the first failing entry.
+    pub fn replay(&mut self, trace: &[TraceEntry<T>]) -> Result<(), CallbackError> {
+        for entry in trace {
+            self.run_all(entry.params.clone())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    static OUTPUT: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    fn func0() {
+        OUTPUT.lock().unwrap().push("calling func0".to_string());
+    }
+
+    fn func1(p1: i32) {
+        OUTPUT.lock().unwrap().push(format!("calling func1: {}", p1));
+    }
+
+    fn func2(p1: i32, p2: i32) {
+        OUTPUT.lock().unwrap().push(format!("calling func2: {}, {}", p1, p2));
+    }
+
+    fn func3(p1: i32, p2: i32, p3: i32) {
+        OUTPUT.lock().unwrap().push(format!("calling func3: {}, {}, {}", p1, p2, p3));
+    }
+
+    fn func4(p1: i32, p2: i32, p3: i32, p4: i32) {
+        OUTPUT.lock().unwrap().push(format!("calling func4: {}, {}, {}, {}", p1, p2, p3, p4));
+    }
+
+    #[test]
+    #[allow(unused_variables)]
+    fn test_callback_manager() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        assert_eq!(callback_manager.handlers.len(), 0);
+        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(func0))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(func1))).unwrap();
+        {
+            let h3 = callback_manager.add(CallbackHandler::Callback2(Box::new(func2))).unwrap();
+        }
+        let h4 = callback_manager.add(CallbackHandler::Callback3(Box::new(func3))).unwrap();
+        let h5 = callback_manager.add(CallbackHandler::Callback4(Box::new(func4))).unwrap();
 
         if let Err(err) = callback_manager.run_all(vec![
             CallbackParams::CallParams0(),
@@ -405,41 +3797,1567 @@ mod tests {
             CallbackParams::CallParams3(1, 2, 3),
             CallbackParams::CallParams4(1, 2, 3, 4),
         ]) {
-            assert_eq!(err, "mismatched param counts to active handlers".to_string());
+            assert_eq!(err, CallbackError::ParamCountMismatch { expected: 4, got: 5 });
+        } else {
+            panic!("should return error but not");
+        }
+
+        if let Err(err) = callback_manager.run_all(
+            vec![
+                CallbackParams::CallParams0(),
+                CallbackParams::CallParams2(1, 2),  // mismatching
+                CallbackParams::CallParams2(1, 2),  // mismatching
+                CallbackParams::CallParams4(1, 2, 3, 4),
+            ]
+        ) {
+            assert_eq!(
+                err,
+                CallbackError::ParamTypeMismatch {
+                    mismatches: vec![
+                        ArityMismatch { index: 1, id: HandlerId(1), expected: 1, got: 2 },
+                        ArityMismatch { index: 2, id: HandlerId(3), expected: 3, got: 2 },
+                    ]
+                }
+            );
+            assert_eq!(
+                err.to_string(),
+                "mismatching params for 2 handlers: handler 1 at index 1 expects 1 params, got 2; handler 3 at index 2 expects 3 params, got 2"
+            );
         } else {
             panic!("should return error but not");
         }
 
-        if let Err(err) = callback_manager.run_all(
-            vec![
+        callback_manager.run_all(vec![
+            CallbackParams::CallParams0(),
+            CallbackParams::CallParams1(1),
+            CallbackParams::CallParams3(1, 2, 3),
+            CallbackParams::CallParams4(1, 2, 3, 4),
+        ]).unwrap();
+
+        assert_eq!(
+            *OUTPUT.lock().unwrap(),
+            vec![
+                "calling func0".to_string(),
+                "calling func1: 1".to_string(),
+                "calling func3: 1, 2, 3".to_string(),
+                "calling func4: 1, 2, 3, 4".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_all() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_| {}))).unwrap();
+        let h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        {
+            let _h4 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        }
+
+        let found = callback_manager.find_all(|info| info.arity == 0);
+
+        assert_eq!(found.len(), 2);
+        assert!(Arc::ptr_eq(&found[0], &h1));
+        assert!(Arc::ptr_eq(&found[1], &h3));
+    }
+
+    #[test]
+    fn test_trace_and_replay() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let recorded = {
+            let mut callback_manager = CallbackManager::<i32>::new();
+            let output0 = Arc::clone(&output);
+            let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output0.lock().unwrap().push(x)))).unwrap();
+            let output1 = Arc::clone(&output);
+            let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output1.lock().unwrap().push(x + y)))).unwrap();
+
+            callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams2(2, 3)]).unwrap();
+            callback_manager.enable_tracing();
+            callback_manager.run_all(vec![CallbackParams::CallParams1(4), CallbackParams::CallParams2(5, 6)]).unwrap();
+            callback_manager.run_all(vec![CallbackParams::CallParams1(7)]).unwrap_err();
+
+            callback_manager.take_trace()
+        };
+
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0].ids, vec![HandlerId(0), HandlerId(1)]);
+        assert_eq!(recorded[1].params, vec![CallbackParams::CallParams1(7)]);
+        assert_eq!(recorded[1].ids, vec![]);
+
+        let mut replayed = CallbackManager::<i32>::new();
+        let output0 = Arc::clone(&output);
+        let _h1 = replayed.add(CallbackHandler::Callback1(Box::new(move |x| output0.lock().unwrap().push(x * 10)))).unwrap();
+        let output1 = Arc::clone(&output);
+        let _h2 = replayed.add(CallbackHandler::Callback2(Box::new(move |x, y| output1.lock().unwrap().push((x + y) * 10)))).unwrap();
+
+        assert!(replayed.replay(&recorded[..1]).is_ok());
+        assert_eq!(*output.lock().unwrap(), vec![1, 5, 4, 11, 40, 110]);
+    }
+
+    #[test]
+    fn test_order_by() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..4 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+
+        callback_manager.set_order_by(|info| if info.id.0 % 2 == 0 { 1 } else { 0 });
+        callback_manager.run_all((0..4).map(CallbackParams::CallParams1).collect()).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(1, 0), (3, 1), (0, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        assert!(Arc::ptr_eq(&callback_manager.get_expect(0), &h1));
+        assert!(Arc::ptr_eq(&callback_manager.get(1).unwrap(), &h2));
+
+        drop(h2);
+        assert!(callback_manager.get(1).is_none());
+        assert!(callback_manager.get(2).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "handler 1 at index 1 has been dropped")]
+    fn test_get_expect_dropped() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        callback_manager.get_expect(1);
+    }
+
+    #[test]
+    fn test_chain() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let managers = (0..3).map(|_| Arc::new(sync::Mutex::new(CallbackManager::<i32>::new()))).collect::<Vec<_>>();
+        let mut handles = vec![];
+        for (n, manager) in managers.iter().enumerate() {
+            let output = Arc::clone(&output);
+            handles.push(sync::lock(manager).unwrap().add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+        sync::lock(&managers[0]).unwrap().chain(Arc::downgrade(&managers[1]));
+        sync::lock(&managers[1]).unwrap().chain(Arc::downgrade(&managers[2]));
+        sync::lock(&managers[2]).unwrap().chain(Arc::downgrade(&managers[0]));
+
+        sync::lock(&managers[0]).unwrap().run_all(vec![CallbackParams::CallParams1(7)]).unwrap();
+        sync::lock(&managers[1]).unwrap().run_all(vec![CallbackParams::CallParams1(8)]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 7), (1, 7), (2, 7), (1, 8), (2, 8), (0, 8)]);
+    }
+
+    #[test]
+    fn test_chain_waits_for_locked_manager() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let child = Arc::new(sync::Mutex::new(CallbackManager::<i32>::new()));
+        let diamond = Arc::new(sync::Mutex::new(CallbackManager::<i32>::new()));
+        let output1 = Arc::clone(&output);
+        let _h1 = sync::lock(&child).unwrap().add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(("child", x))))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = sync::lock(&diamond).unwrap().add(CallbackHandler::Callback1(Box::new(move |x| output2.lock().unwrap().push(("diamond", x))))).unwrap();
+        let _h3 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        callback_manager.chain(Arc::downgrade(&child));
+        callback_manager.chain(Arc::downgrade(&diamond));
+        sync::lock(&child).unwrap().chain(Arc::downgrade(&diamond));
+
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let holder = {
+            let child = Arc::clone(&child);
+            std::thread::spawn(move || {
+                let _guard = sync::lock(&child).unwrap();
+                locked_tx.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(50));
+            })
+        };
+        locked_rx.recv().unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+        holder.join().unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![("child", 1), ("diamond", 1)]);
+    }
+
+    #[test]
+    fn test_bind() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+
+        let output0 = Arc::clone(&output);
+        let handler = CallbackHandler::Callback3(Box::new(move |x, y, z| output0.lock().unwrap().push(vec![x, y, z])));
+        let _h1 = callback_manager.add(handler.bind_last(30).unwrap()).unwrap();
+        let output1 = Arc::clone(&output);
+        let handler = CallbackHandler::Callback3(Box::new(move |x, y, z| output1.lock().unwrap().push(vec![x, y, z])));
+        let _h2 = callback_manager.add(handler.bind_first(10).unwrap().bind_first(20).unwrap()).unwrap();
+        let output2 = Arc::clone(&output);
+        let handler = CallbackHandler::Callback1(Box::new(move |x| output2.lock().unwrap().push(vec![x])));
+        let _h3 = callback_manager.add(handler.bind_last(40).unwrap()).unwrap();
+
+        assert!(CallbackHandler::<i32>::Callback0(Box::new(|| {})).bind_first(1).is_none());
+
+        callback_manager.run_all(vec![
+            CallbackParams::CallParams2(1, 2),
+            CallbackParams::CallParams1(3),
+            CallbackParams::CallParams0(),
+        ]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![vec![1, 2, 30], vec![10, 20, 3], vec![40]]);
+    }
+
+    #[test]
+    fn test_extend_returning() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let handles = callback_manager.extend_returning((0..5).map(|_| CallbackHandler::Callback1(Box::new(|_| {})))).unwrap();
+
+        assert_eq!(handles.len(), 5);
+        assert_eq!(callback_manager.active_count(), 5);
+        for (handle, entry) in handles.iter().zip(callback_manager.handlers.iter()) {
+            assert!(Arc::ptr_eq(handle, &entry.handler.upgrade().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_replay_last() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        callback_manager.set_replay_last(true);
+
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(|_, _| {}))).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(5), CallbackParams::CallParams2(1, 2)]).unwrap();
+
+        let output2 = Arc::clone(&output);
+        let _h3 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output2.lock().unwrap().push(vec![x, y])))).unwrap();
+        let output3 = Arc::clone(&output);
+        let _h4 = callback_manager.add(CallbackHandler::Callback3(Box::new(move |x, y, z| output3.lock().unwrap().push(vec![x, y, z])))).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![vec![1, 2]]);
+
+        callback_manager.set_replay_last(false);
+        let output1 = Arc::clone(&output);
+        let _h5 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(vec![x])))).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    #[cfg(feature = "test-util")]
+    fn test_record_order() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let order = callback_manager.record_order();
+
+        callback_manager.set_order_by(|info| -(info.id.0 as i64));
+        callback_manager.run_all(vec![CallbackParams::CallParams0(); 3]).unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec![HandlerId(2), HandlerId(1), HandlerId(0)]);
+    }
+
+    #[test]
+    fn test_run_all_bucketed() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..4 {
+            let output = Arc::clone(&output);
+            handles.push(if n % 2 == 0 {
+                callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push(vec![x])))).unwrap()
+            } else {
+                callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output.lock().unwrap().push(vec![x, y])))).unwrap()
+            });
+        }
+
+        let mut buckets = HashMap::from([
+            (1, vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]),
+            (2, vec![CallbackParams::CallParams2(3, 4)]),
+        ]);
+        assert_eq!(
+            callback_manager.run_all_bucketed(buckets.clone()),
+            Err(CallbackError::BucketSizeMismatch { arity: 2, expected: 2, got: 1 })
+        );
+
+        buckets.get_mut(&2).unwrap().push(CallbackParams::CallParams2(5, 6));
+        callback_manager.run_all_bucketed(buckets).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![vec![1], vec![3, 4], vec![2], vec![5, 6]]);
+    }
+
+    #[test]
+    fn test_run_some() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..4 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+        handles.remove(1);
+
+        assert_eq!(
+            callback_manager.run_some(&[HandlerId(1), HandlerId(2), HandlerId(9)], vec![CallbackParams::CallParams1(0); 3]),
+            Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(1), HandlerId(9)] })
+        );
+        assert_eq!(
+            callback_manager.run_some(&[HandlerId(3), HandlerId(0)], vec![CallbackParams::CallParams1(0), CallbackParams::CallParams0()]),
+            Err(CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index: 1, id: HandlerId(0), expected: 1, got: 0 }]
+            })
+        );
+
+        callback_manager.run_some(&[HandlerId(3), HandlerId(0)], vec![CallbackParams::CallParams1(30), CallbackParams::CallParams1(0)]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(3, 30), (0, 0)]);
+    }
+
+    #[test]
+    fn test_verify_against() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_| {}))).unwrap();
+        let snapshot = callback_manager.snapshot();
+
+        drop(h1);
+        let _h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        assert_eq!(
+            callback_manager.verify_against(&snapshot),
+            Err(CallbackError::SnapshotMismatch {
+                expected: vec![(HandlerId(0), 0), (HandlerId(1), 1)],
+                actual: vec![(HandlerId(1), 1), (HandlerId(2), 0)],
+            })
+        );
+    }
+
+    #[test]
+    fn test_param_count_policy() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..3 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+        let params = |count| (0..count).map(|x| CallbackParams::CallParams1(x * 10)).collect::<Vec<_>>();
+
+        assert_eq!(
+            callback_manager.run_all_with_policy(params(2), ParamCountPolicy::AtLeast),
+            Err(CallbackError::ParamCountMismatch { expected: 3, got: 2 })
+        );
+        assert_eq!(
+            callback_manager.run_all_with_policy(params(4), ParamCountPolicy::AtMost),
+            Err(CallbackError::ParamCountMismatch { expected: 3, got: 4 })
+        );
+        callback_manager.run_all_with_policy(params(4), ParamCountPolicy::AtLeast).unwrap();
+        callback_manager.run_all_with_policy(params(2), ParamCountPolicy::AtMost).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 0), (1, 10), (2, 20), (0, 0), (1, 10)]);
+    }
+
+    #[test]
+    fn test_map_arg() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+
+        let output0 = Arc::clone(&output);
+        let handler = CallbackHandler::<&str>::Callback3(Box::new(move |x, y, z| output0.lock().unwrap().push(format!("{x}{y}{z}"))));
+        let _h1 = callback_manager.add(handler.map_arg(|x: i32| if x % 2 == 0 { "even" } else { "odd" })).unwrap();
+        let output1 = Arc::clone(&output);
+        let handler = CallbackHandler::<u8>::Callback0(Box::new(move || output1.lock().unwrap().push("none".to_string())));
+        let _h2 = callback_manager.add(handler.map_arg(|x: i32| x as u8)).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams3(1, 2, 3), CallbackParams::CallParams0()]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec!["oddevenodd".to_string(), "none".to_string()]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "parking_lot"))]
+    fn test_poisoned_count() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| panic!("callback failure")))).unwrap();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(
+            || callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()])
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(callback_manager.poisoned_count(), 1);
+        assert_eq!(callback_manager.len_by_state(), ManagerStats { active: 2, dead: 0, poisoned: 1 });
+        assert_eq!(
+            callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()]),
+            Err(CallbackError::LockPoisoned)
+        );
+    }
+
+    #[test]
+    fn test_run_all_consume() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<Vec<i32>>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x: Vec<i32>| output1.lock().unwrap().push(x)))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |mut x: Vec<i32>, y| {
+            x.extend(y);
+            output2.lock().unwrap().push(x);
+        }))).unwrap();
+
+        assert_eq!(
+            callback_manager.run_all_consume(vec![CallbackParams::CallParams1(vec![1])]),
+            Err(CallbackError::ParamCountMismatch { expected: 2, got: 1 })
+        );
+        callback_manager.run_all_consume(vec![
+            CallbackParams::CallParams1(vec![1]),
+            CallbackParams::CallParams2(vec![2], vec![3, 4]),
+        ]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![vec![1], vec![2, 3, 4]]);
+    }
+
+    #[test]
+    fn test_insert() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..2 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+        let output2 = Arc::clone(&output);
+        handles.push(callback_manager.insert(1, CallbackHandler::Callback1(Box::new(move |x| output2.lock().unwrap().push((2, x))))).unwrap());
+        let output3 = Arc::clone(&output);
+        handles.push(callback_manager.insert(10, CallbackHandler::Callback1(Box::new(move |x| output3.lock().unwrap().push((3, x))))).unwrap());
+
+        callback_manager.run_all(vec![
+            CallbackParams::CallParams1(10),
+            CallbackParams::CallParams1(11),
+            CallbackParams::CallParams1(12),
+            CallbackParams::CallParams1(13),
+        ]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 10), (2, 11), (1, 12), (3, 13)]);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(0), HandlerId(2), HandlerId(1), HandlerId(3)]);
+    }
+
+    #[test]
+    fn test_add_batch_checked() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let batch = || vec![
+            CallbackHandler::Callback2(Box::new(|_x, _y| {})),
+            CallbackHandler::Callback0(Box::new(|| {})),
+        ];
+
+        assert_eq!(
+            callback_manager.add_batch_checked(batch(), &[2]).err(),
+            Some(CallbackError::TemplateMismatch { expected: vec![2], actual: vec![2, 0] })
+        );
+        assert_eq!(callback_manager.active_count(), 0);
+
+        let _handles = callback_manager.add_batch_checked(batch(), &[2, 0]).unwrap();
+        assert_eq!(callback_manager.snapshot(), vec![(HandlerId(0), 2), (HandlerId(1), 0)]);
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        callback_manager.set_observer(tx);
+
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap_err();
+
+        let events = rx.try_iter().collect::<Vec<DispatchEvent>>();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].ids_invoked, vec![HandlerId(0), HandlerId(1)]);
+        assert_eq!(events[0].error, None);
+        assert_eq!(events[1].ids_invoked, vec![]);
+        assert_eq!(events[1].error, Some(CallbackError::ParamCountMismatch { expected: 2, got: 1 }));
+
+        drop(rx);
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).is_ok());
+    }
+
+    #[test]
+    fn test_params_from_array() {
+        assert_eq!(CallbackParams::<i32>::from_array([]), CallbackParams::CallParams0());
+        assert_eq!(
+            CallbackParams::from_array([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]),
+            CallbackParams::CallParams12(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12)
+        );
+        assert_eq!(CallbackParams::try_from(&[1; 12][..]).map(|params| params.arity()), Ok(12));
+        assert_eq!(CallbackParams::try_from(&[1; 13][..]), Err(CallbackError::InvalidArity { arity: 13 }));
+        assert_eq!(
+            CallbackParams::try_from(vec!["a".to_string()]),
+            Ok(CallbackParams::CallParams1("a".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported arity 13")]
+    fn test_params_from_array_too_long() {
+        CallbackParams::from_array([0; 13]);
+    }
+
+    #[test]
+    fn test_multi_arity_handler() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (output1, output2, output3) = (Arc::clone(&output), Arc::clone(&output), Arc::clone(&output));
+        let multi = MultiArityHandler::new(vec![
+            CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(vec![x]))),
+            CallbackHandler::Callback2(Box::new(move |x, y| output2.lock().unwrap().push(vec![x, y]))),
+            CallbackHandler::Callback2(Box::new(|_x, _y| unreachable!())),
+        ]);
+        assert_eq!(multi.arities(), [1, 2]);
+        let multi = MultiArityHandler::new(vec![
+            CallbackHandler::Multi(multi),
+            CallbackHandler::Callback3(Box::new(move |x, y, z| output3.lock().unwrap().push(vec![x, y, z]))),
+        ]);
+        let _h1 = callback_manager.add(CallbackHandler::Multi(multi).map_arg(|x: i32| x * 10)).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(3)]).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams3(4, 5, 6)]).unwrap();
+        assert_eq!(
+            callback_manager.run_all(vec![CallbackParams::CallParams0()]),
+            Err(CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index: 0, id: HandlerId(0), expected: 3, got: 0 }]
+            })
+        );
+        assert_eq!(*output.lock().unwrap(), vec![vec![10, 20], vec![30], vec![40, 50, 60]]);
+
+        let bound = MultiArityHandler::new(vec![
+            CallbackHandler::Callback0(Box::new(|| {})),
+            CallbackHandler::Callback2(Box::new(|_x: i32, _y| {})),
+        ]);
+        assert_eq!(CallbackHandler::Multi(bound).bind_first(1).map(|handler| handler.arity()), Some(1));
+    }
+
+    #[test]
+    fn test_run_all_gen() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<u64>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        drop(_h2);
+        let output3 = Arc::clone(&output);
+        let _h3 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output3.lock().unwrap().push(x + y)))).unwrap();
+
+        callback_manager.run_all_gen(|id, index| match index {
+            0 => CallbackParams::CallParams1(id.0 + 10),
+            _ => CallbackParams::CallParams2(id.0, index as u64 * 100),
+        }).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![10, 102]);
+
+        assert_eq!(
+            callback_manager.run_all_gen(|_id, _index| CallbackParams::CallParams1(0)),
+            Err(CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index: 1, id: HandlerId(2), expected: 2, got: 1 }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_freeze() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h1 = callback_manager.add_keyed("key", CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        callback_manager.freeze();
+
+        assert_eq!(
+            callback_manager.add_batch_checked(vec![CallbackHandler::Callback0(Box::new(|| {}))], &[0]).err(),
+            Some(CallbackError::Frozen)
+        );
+        assert_eq!(callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).err(), Some(CallbackError::Frozen));
+        assert_eq!(callback_manager.insert(0, CallbackHandler::Callback0(Box::new(|| {}))).err(), Some(CallbackError::Frozen));
+        assert_eq!(callback_manager.add_with_id(CallbackHandler::Callback0(Box::new(|| {}))).err(), Some(CallbackError::Frozen));
+        assert_eq!(callback_manager.add1(|_x| {}).err().map(|error| error.to_string()), Some(CallbackError::Frozen.to_string()));
+        assert_eq!(callback_manager.replace_all(vec![]).err(), Some(CallbackError::Frozen));
+
+        assert_eq!(callback_manager.remove(&h1), Err(CallbackError::Frozen));
+        assert_eq!(callback_manager.remove_by_id(HandlerId(0)), Err(CallbackError::Frozen));
+        assert_eq!(callback_manager.remove_by_key(&"key"), Err(CallbackError::Frozen));
+        assert_eq!(callback_manager.retain_by_id(|_id, _key: Option<&()>| false), Err(CallbackError::Frozen));
+        assert_eq!(callback_manager.pop().err(), Some(CallbackError::Frozen));
+        assert_eq!(callback_manager.clear(), Err(CallbackError::Frozen));
+        assert_eq!(callback_manager.split_off(0).err(), Some(CallbackError::Frozen));
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(0)]);
+        assert!(callback_manager.is_active(&h1));
+    }
+
+    #[test]
+    fn test_catch_all() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(move |arity, args| output1.lock().unwrap().push((arity, args))))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output2.lock().unwrap().push((2, vec![x * y]))))).unwrap();
+        let _h3 = callback_manager.insert(0, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(2), HandlerId(1), HandlerId(0)]);
+        callback_manager.enable_tracing();
+        callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams2(3, 4)]).unwrap();
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams0()]).is_err());
+
+        assert_eq!(*output.lock().unwrap(), vec![(2, vec![12]), (0, vec![]), (2, vec![3, 4])]);
+        assert_eq!(callback_manager.take_trace()[0].ids, vec![HandlerId(2), HandlerId(1), HandlerId(0)]);
+    }
+
+    #[test]
+    fn test_is_active() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+
+        assert!(callback_manager.is_active(&h0) && callback_manager.is_active(&h1) && callback_manager.is_active(&h2));
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(0), HandlerId(2), HandlerId(1)]);
+
+        drop(h1);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(0), HandlerId(2)]);
+        callback_manager.remove(&h2).unwrap();
+        assert!(!callback_manager.is_active(&h2));
+    }
+
+    #[test]
+    fn test_handler_id_as_key() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.insert(0, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        let names = callback_manager.active_ids().into_iter().zip(["second", "first"]).collect::<HashMap<HandlerId, &str>>();
+        assert_eq!(names[&HandlerId(0)], "first");
+        assert_eq!(names[&HandlerId(1)], "second");
+
+        let mut ids = callback_manager.active_ids();
+        ids.sort();
+        assert_eq!(ids, vec![HandlerId(0), HandlerId(1)]);
+    }
+
+    #[test]
+    fn test_index_of() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+
+        assert_eq!(callback_manager.index_of(&h0), Some(0));
+        assert_eq!(callback_manager.index_of(&h2), Some(1));
+        assert_eq!(callback_manager.index_of(&h1), None);
+        assert!(callback_manager.is_active(&h1));
+    }
+
+    #[test]
+    fn test_run_all_scoped() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let mut calls = 0;
+        let _h1 = callback_manager.add(CallbackHandler::Cloneable(CloneableCallback::new(2, move |args: &[i32]| {
+            calls += 1;
+            output1.lock().unwrap().push((calls, args[0] + args[1]));
+        }).unwrap())).unwrap();
+
+        let params = vec![CallbackParams::CallParams2(1, 2)];
+        callback_manager.run_all_scoped(params.clone()).unwrap();
+        callback_manager.run_all_scoped(params.clone()).unwrap();
+        callback_manager.run_all(params.clone()).unwrap();
+        callback_manager.run_all_scoped(params).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![(1, 3), (1, 3), (1, 3), (2, 3)]);
+
+        let h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        assert_eq!(
+            callback_manager.run_all_scoped(vec![CallbackParams::CallParams2(1, 2), CallbackParams::CallParams0()]),
+            Err(CallbackError::NotCloneable { ids: vec![HandlerId(1)] })
+        );
+        drop(h2);
+        let _h3 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        assert_eq!(
+            callback_manager.run_all_scoped(vec![CallbackParams::CallParams2(1, 2)]),
+            Err(CallbackError::NotCloneable { ids: vec![HandlerId(2)] })
+        );
+        assert!(CloneableCallback::<i32>::new(13, |_args: &[i32]| {}).is_err());
+    }
+
+    #[test]
+    fn test_run_all_truncate() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(vec![x])))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output2.lock().unwrap().push(vec![x, y])))).unwrap();
+        let _h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        callback_manager.run_all_truncate(vec![
+            CallbackParams::CallParams3(1, 2, 3),
+            CallbackParams::CallParams2(4, 5),
+            CallbackParams::CallParams1(6),
+        ]).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![vec![1], vec![4, 5]]);
+
+        assert_eq!(
+            callback_manager.run_all_truncate(vec![
+                CallbackParams::CallParams1(1),
+                CallbackParams::CallParams1(2),
                 CallbackParams::CallParams0(),
-                CallbackParams::CallParams2(1, 2),  // mismatching
-                CallbackParams::CallParams2(1, 2),  // mismatching
-                CallbackParams::CallParams4(1, 2, 3, 4),
-            ]
-        ) {
-            assert_eq!(err, "mismatching params for 2 handlers".to_string());
-        } else {
-            panic!("should return error but not");
+            ]),
+            Err(CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index: 1, id: HandlerId(1), expected: 2, got: 1 }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_clone_structure_with_noops() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h1 = callback_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| panic!("side effect")))).unwrap();
+        drop(callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+        let _h3 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("side effect")))).unwrap();
+
+        let (mut noop_manager, noops) = callback_manager.clone_structure_with_noops();
+        assert_eq!(noops.len(), 2);
+        assert_eq!(noop_manager.active_ids(), vec![HandlerId(0), HandlerId(2)]);
+        noop_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).unwrap();
+
+        let _h4 = noop_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        assert_eq!(noop_manager.active_ids(), vec![HandlerId(0), HandlerId(3), HandlerId(2)]);
+    }
+
+    #[test]
+    fn test_validator() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(move |_arity, args| output2.lock().unwrap().extend(args)))).unwrap();
+        callback_manager.set_validator(HandlerId(0), |args| args[0] > 0).unwrap();
+        callback_manager.set_validator(HandlerId(1), |args| args.first() != Some(&2)).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+        assert_eq!(callback_manager.vetoed(), []);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(-1)]).unwrap();
+        assert_eq!(callback_manager.vetoed(), [HandlerId(0)]);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(2)]).unwrap();
+        assert_eq!(callback_manager.vetoed(), [HandlerId(1)]);
+        assert_eq!(*output.lock().unwrap(), vec![1, 1, -1, 2]);
+
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams0()]).is_err());
+        assert_eq!(
+            callback_manager.set_validator(HandlerId(5), |_args| true),
+            Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(5)] })
+        );
+    }
+
+    #[test]
+    fn test_validator_borrowing_local() {
+        let blocked = [2, 3];
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (id, _h0) = callback_manager.add_with_id(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        callback_manager.set_validator(id, |args| !blocked.contains(&args[0])).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams1(3)]).unwrap();
+        assert_eq!(callback_manager.vetoed(), [id]);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(4)]).unwrap();
+        assert_eq!(callback_manager.vetoed(), []);
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        drop(callback_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap());
+
+        let popped = callback_manager.pop().unwrap().unwrap();
+        assert!(Arc::ptr_eq(&popped, &h2));
+        assert!(!callback_manager.is_active(&h2));
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams0()]).is_ok());
+
+        let h4 = callback_manager.add(CallbackHandler::Callback2(Box::new(|_x, _y| {}))).unwrap();
+        assert!(Arc::ptr_eq(&callback_manager.pop().unwrap().unwrap(), &h4));
+        assert!(Arc::ptr_eq(&callback_manager.pop().unwrap().unwrap(), &h1));
+        assert!(callback_manager.pop().unwrap().is_none());
+        assert!(callback_manager.handlers.is_empty());
+
+        let h5 = callback_manager.add_keyed("first", CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h6 = callback_manager.add_keyed("catch_all", CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let h7 = callback_manager.insert(0, CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        callback_manager.set_remember_params(true);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).unwrap();
+        assert!(Arc::ptr_eq(&callback_manager.pop().unwrap().unwrap(), &h7));
+        assert!(Arc::ptr_eq(&callback_manager.pop().unwrap().unwrap(), &h6));
+        assert!(Arc::ptr_eq(&callback_manager.pop().unwrap().unwrap(), &h5));
+        assert!(callback_manager.keys.is_empty());
+        assert!(callback_manager.remembered.as_ref().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_noop() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let placeholder = callback_manager.add_noop(1).unwrap();
+        let _h2 = callback_manager.add_noop(0).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams0()]).unwrap();
+
+        let output1 = Arc::clone(&output);
+        *sync::lock(&placeholder).unwrap() = CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)));
+        callback_manager.run_all(vec![CallbackParams::CallParams1(2), CallbackParams::CallParams0()]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![2]);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(0), HandlerId(1)]);
+        assert_eq!(callback_manager.add_noop(13).err(), Some(CallbackError::InvalidArity { arity: 13 }));
+    }
+
+    #[test]
+    fn test_remove_by_key() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for key in ["audit", "ui", "audit"] {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add_keyed(key.to_string(), CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((key, x))))).unwrap());
+        }
+        let _h4 = callback_manager.add_keyed(1u8, CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+
+        assert!(callback_manager.remove_by_key(&"audit".to_string()).unwrap());
+        assert!(!callback_manager.remove_by_key(&"audit".to_string()).unwrap());
+        assert!(!callback_manager.remove_by_key(&"ui").unwrap());
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![("ui", 1)]);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(1), HandlerId(3)]);
+        assert!(callback_manager.remove_by_key(&1u8).unwrap());
+    }
+
+    #[test]
+    fn test_run_all_deadline() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("past the deadline")))).unwrap();
+
+        let params = vec![CallbackParams::CallParams1(1)];
+        assert_eq!(callback_manager.run_all_deadline(params.clone(), Instant::now()), Ok(vec![HandlerId(0), HandlerId(1)]));
+        assert_eq!(
+            callback_manager.run_all_deadline(vec![], Instant::now() + Duration::from_secs(60)),
+            Err(CallbackError::ParamCountMismatch { expected: 1, got: 0 })
+        );
+        assert!(output.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_const_arity() {
+        const PARAMS: CallbackParams<i32> = CallbackParams::CallParams3(1, 2, 3);
+        const ARITY: usize = PARAMS.arity();
+        const _: () = assert!(PARAMS.arity() == 3);
+
+        let args = [0; ARITY];
+        assert_eq!(CallbackParams::from_array(args), CallbackParams::CallParams3(0, 0, 0));
+    }
+
+    #[test]
+    fn test_is_arity() {
+        const _: () = assert!(CallbackParams::CallParams2(1, 2).is_arity(2));
+        let handlers = [
+            CallbackHandler::<i32>::Callback0(Box::new(|| {})),
+            CallbackHandler::Callback1(Box::new(|_x| {})),
+            CallbackHandler::Multi(MultiArityHandler::new(vec![
+                CallbackHandler::Callback0(Box::new(|| {})),
+                CallbackHandler::Callback3(Box::new(|_x, _y, _z| {})),
+            ])),
+        ];
+
+        assert_eq!(handlers.iter().filter(|handler| handler.is_arity(0)).count(), 1);
+        assert!(handlers[1].is_arity(1) && !handlers[1].is_arity(0));
+        assert!(handlers[2].is_arity(3));
+        assert!(!CallbackParams::CallParams1(1).is_arity(0));
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output_old = Arc::clone(&output);
+        let _old = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output_old.lock().unwrap().push(-x)))).unwrap();
+        let _old_catch_all = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("replaced")))).unwrap();
+
+        let output1 = Arc::clone(&output);
+        let output2 = Arc::clone(&output);
+        let new = callback_manager.replace_all(vec![
+            CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x))),
+            CallbackHandler::Callback2(Box::new(move |x, y| output2.lock().unwrap().push(x + y))),
+        ]).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams2(2, 3)]).unwrap();
+
+        assert_eq!(new.len(), 2);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(2), HandlerId(3)]);
+        assert_eq!(*output.lock().unwrap(), vec![1, 5]);
+    }
+
+    #[test]
+    fn test_add_rate_limited() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add_rate_limited(5, CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let _h2 = callback_manager.add_rate_limited(0, CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("rate limited")))).unwrap();
+
+        for x in 0..100 {
+            callback_manager.run_all(vec![CallbackParams::CallParams1(x)]).unwrap();
         }
 
+        assert_eq!(*output.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(callback_manager.rate_limited_drops(HandlerId(0)), Some(95));
+        assert_eq!(callback_manager.rate_limited_drops(HandlerId(1)), Some(100));
+        assert_eq!(callback_manager.rate_limited_drops(HandlerId(2)), None);
+    }
+
+    #[test]
+    fn test_run_all_cycle() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _handlers = (0..5).map(|_| {
+            let output = Arc::clone(&output);
+            callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push(x)))).unwrap()
+        }).collect::<Vec<_>>();
+
+        let params = [CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)];
+        callback_manager.run_all_cycle(&params).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![1, 2, 1, 2, 1]);
+
+        let _h5 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let err = callback_manager.run_all_cycle(&params).unwrap_err();
+        assert_eq!(err, CallbackError::ParamTypeMismatch {
+            mismatches: vec![ArityMismatch { index: 5, id: HandlerId(5), expected: 0, got: 1 }],
+        });
+        assert_eq!(output.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_add_forwarding() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _h0 = callback_manager.add_forwarding(0, tx.clone()).unwrap();
+        let _h3 = callback_manager.add_forwarding(3, tx).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams3(1, 2, 3)]).unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<Vec<i32>>>(), vec![vec![], vec![1, 2, 3]]);
+
+        drop(rx);
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams3(1, 2, 3)]).is_ok());
+        assert_eq!(callback_manager.add_forwarding(13, std::sync::mpsc::channel().0).err(), Some(CallbackError::InvalidArity { arity: 13 }));
+    }
+
+    #[test]
+    fn test_split_off() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handlers = (0..4).map(|i| {
+            let output = Arc::clone(&output);
+            callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((i, x))))).unwrap()
+        }).collect::<Vec<_>>();
+        handlers.remove(1);
+        let _last = callback_manager.add_keyed("last",CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        let mut other_manager = callback_manager.split_off(2).unwrap();
+        assert!(!callback_manager.remove_by_key(&"last").unwrap());
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]).unwrap();
+        other_manager.run_all(vec![CallbackParams::CallParams1(3), CallbackParams::CallParams0()]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 1), (2, 2), (3, 3)]);
+        assert!(other_manager.remove_by_key(&"last").unwrap());
+        let _h5 = other_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        assert_eq!(other_manager.active_ids(), vec![HandlerId(3), HandlerId(5)]);
+    }
+
+    #[test]
+    fn test_chain2() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (output_a, output_b) = (Arc::clone(&output), Arc::clone(&output));
+        let chained = CallbackHandler::chain2(
+            CallbackHandler::Callback2(Box::new(move |x, y| output_a.lock().unwrap().push(("a", x, y)))),
+            CallbackHandler::Callback2(Box::new(move |x, y| output_b.lock().unwrap().push(("b", x, y)))),
+        ).unwrap();
+        let _h = callback_manager.add(chained).unwrap();
+
+        callback_manager.run_all(vec![CallbackParams::CallParams2(1, 2)]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![("a", 1, 2), ("b", 1, 2)]);
+        assert!(CallbackHandler::<i32>::chain2(
+            CallbackHandler::Callback1(Box::new(|_x| {})),
+            CallbackHandler::Callback2(Box::new(|_x, _y| {})),
+        ).is_none());
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let push = |id: u64| {
+            let output = Arc::clone(&output);
+            move || output.lock().unwrap().push(HandlerId(id))
+        };
+        let (push0, push2, push4, push5) = (push(0), push(2), push(4), push(5));
+        let _h0 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |_x, _y| push0()))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback0(Box::new(push2))).unwrap();
+        drop(callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+        let _h4 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |_x| push4()))).unwrap();
+        let _h5 = callback_manager.add(CallbackHandler::Callback0(Box::new(push5))).unwrap();
+        assert_eq!(callback_manager.dispatch_order(), vec![HandlerId(0), HandlerId(2), HandlerId(4), HandlerId(5), HandlerId(1)]);
+
+        callback_manager.set_order_by(|info| info.arity as i64);
+        let order = callback_manager.dispatch_order();
+        assert_eq!(order, vec![HandlerId(2), HandlerId(5), HandlerId(4), HandlerId(0), HandlerId(1)]);
+
         callback_manager.run_all(vec![
+            CallbackParams::CallParams0(),
             CallbackParams::CallParams0(),
             CallbackParams::CallParams1(1),
-            CallbackParams::CallParams3(1, 2, 3),
-            CallbackParams::CallParams4(1, 2, 3, 4),
+            CallbackParams::CallParams2(1, 2),
         ]).unwrap();
+        assert_eq!(output.lock().unwrap()[..], order[..4]);
+    }
 
-        unsafe {
-            assert_eq!(
-                OUTPUT,
-                vec![
-                    "calling func0".to_string(),
-                    "calling func1: 1".to_string(),
-                    "calling func3: 1, 2, 3".to_string(),
-                    "calling func4: 1, 2, 3, 4".to_string(),
-                ]
-            );
+    #[cfg(not(feature = "parking_lot"))]
+    #[test]
+    fn test_error_sink() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let errors = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let h0 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| panic!("poison")))).unwrap();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| h0.lock().unwrap().call(&CallbackParams::CallParams1(0))));
+
+        let errors_clone = Arc::clone(&errors);
+        callback_manager.set_error_sink(move |error| errors_clone.lock().unwrap().push(error));
+
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]).is_ok());
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams1(3)]).is_ok());
+        assert_eq!(*output.lock().unwrap(), vec![2]);
+        assert_eq!(*errors.lock().unwrap(), vec![
+            CallbackError::LockPoisoned,
+            CallbackError::ParamCountMismatch { expected: 2, got: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_error_sink_borrowing_local() {
+        let mut errors = vec![];
+        {
+            let mut callback_manager = CallbackManager::<i32>::new();
+            let _h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+            callback_manager.set_error_sink(|error| errors.push(error));
+            assert!(callback_manager.run_all(vec![]).is_ok());
+        }
+        assert_eq!(errors, vec![CallbackError::ParamCountMismatch { expected: 1, got: 0 }]);
+    }
+
+    #[test]
+    fn test_params_try_new() {
+        assert_eq!(CallbackParams::<i32>::try_new(0, &[]), Ok(CallbackParams::CallParams0()));
+        assert_eq!(CallbackParams::try_new(12, &[1; 12]), Ok(CallbackParams::from_array([1; 12])));
+        assert_eq!(CallbackParams::try_new(13, &[1; 13]), Err(CallbackError::InvalidArity { arity: 13 }));
+        assert_eq!(CallbackParams::try_new(13, &[1; 12]), Err(CallbackError::InvalidArity { arity: 13 }));
+        assert_eq!(CallbackParams::try_new(1, &[1, 2]), Err(CallbackError::ArgCountMismatch { expected: 1, got: 2 }));
+        assert_eq!(CallbackParams::<i32>::try_new(2, &[]), Err(CallbackError::ArgCountMismatch { expected: 2, got: 0 }));
+    }
+
+    #[test]
+    fn test_params_get_arg() {
+        let params = CallbackParams::from_array([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(params.get_arg(0), Some(1));
+        assert_eq!(params.get_arg(11), Some(12));
+        assert_eq!(params.get_arg(12), None);
+        assert_eq!(params.arg_count(), 12);
+        assert_eq!(CallbackParams::<i32>::CallParams0().get_arg(0), None);
+        assert_eq!(CallbackParams::CallParams1(7).get_arg(0), Some(7));
+        assert_eq!(CallbackParams::CallParams1(7).get_arg(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_retain_by_id() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add_keyed(true, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h1 = callback_manager.add_keyed(false, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h2 = callback_manager.add_keyed(true, CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("stale")))).unwrap();
+        let _h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h4 = callback_manager.add_keyed(false, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h5 = callback_manager.add_keyed(1, CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        drop(h4);
+
+        let mut seen = vec![];
+        callback_manager.retain_by_id(|id, stale: Option<&bool>| {
+            seen.push(id);
+            stale != Some(&true)
+        }).unwrap();
+
+        assert_eq!(seen, vec![HandlerId(0), HandlerId(1), HandlerId(3), HandlerId(5), HandlerId(2)]);
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(1), HandlerId(3), HandlerId(5)]);
+        assert!(callback_manager.run_all(vec![CallbackParams::CallParams0(); 3]).is_ok());
+        assert!(callback_manager.remove_by_key(&1).unwrap());
+    }
+
+    #[test]
+    fn test_refire() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _handlers = (0..2).map(|i| {
+            let output = Arc::clone(&output);
+            callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((i, x))))).unwrap()
+        }).collect::<Vec<_>>();
+        callback_manager.set_remember_params(true);
+        callback_manager.set_validator(HandlerId(1), |args| args[0] > 0).unwrap();
+
+        assert_eq!(callback_manager.refire(HandlerId(0)), Err(CallbackError::NotInvoked { id: HandlerId(0) }));
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)]).unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams1(3), CallbackParams::CallParams1(0)]).unwrap();
+        callback_manager.refire(HandlerId(0)).unwrap();
+        callback_manager.refire(HandlerId(1)).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 1), (1, 2), (0, 3), (0, 3), (1, 2)]);
+        assert_eq!(callback_manager.refire(HandlerId(2)), Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(2)] }));
+    }
+
+    #[test]
+    fn test_run_all_prioritizing() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _handlers = (0..4).map(|i| {
+            let output = Arc::clone(&output);
+            callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((i, x))))).unwrap()
+        }).collect::<Vec<_>>();
+        let params = (10..14).map(CallbackParams::CallParams1).collect::<Vec<_>>();
+
+        callback_manager.run_all_prioritizing(HandlerId(2), params.clone()).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![(2, 10), (0, 11), (1, 12), (3, 13)]);
+        assert_eq!(callback_manager.dispatch_order(), vec![HandlerId(0), HandlerId(1), HandlerId(2), HandlerId(3)]);
+
+        output.lock().unwrap().clear();
+        callback_manager.run_all(params.clone()).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![(0, 10), (1, 11), (2, 12), (3, 13)]);
+        assert_eq!(
+            callback_manager.run_all_prioritizing(HandlerId(4), params),
+            Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(4)] })
+        );
+    }
+
+    #[test]
+    fn test_run_all_prioritizing_panic_restores_order() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        let _h2 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| panic!("callback failure")))).unwrap();
+        let params = (10..13).map(CallbackParams::CallParams1).collect::<Vec<_>>();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(
+            || callback_manager.run_all_prioritizing(HandlerId(2), params)
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(callback_manager.dispatch_order(), vec![HandlerId(0), HandlerId(1), HandlerId(2)]);
+    }
+
+    #[test]
+    fn test_count_invocations_since() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let started = Instant::now();
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(0), started), None);
+
+        callback_manager.track_invocations(3);
+        for _ in 0..2 {
+            callback_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+        }
+        let since = Instant::now();
+        callback_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(0), since), Some(1));
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(1), started), Some(3));
+        callback_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(0), since), Some(2));
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(0), started), Some(3));
+        assert_eq!(callback_manager.count_invocations_since(HandlerId(2), started), None);
+    }
+
+    #[test]
+    fn test_compact() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new_with_id_base(10);
+        let mut handlers = (0..5).map(|i| {
+            let output = Arc::clone(&output);
+            callback_manager.add_keyed(i, CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((i, x))))).unwrap()
+        }).collect::<Vec<_>>();
+        handlers.remove(3);
+        handlers.remove(0);
+        callback_manager.set_validator(HandlerId(14), |args| args[0] > 0).unwrap();
+
+        let mapping = callback_manager.compact();
+
+        assert_eq!(mapping, HashMap::from([
+            (HandlerId(11), HandlerId(10)),
+            (HandlerId(12), HandlerId(11)),
+            (HandlerId(14), HandlerId(12)),
+        ]));
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(10), HandlerId(11), HandlerId(12)]);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2), CallbackParams::CallParams1(0)]).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![(1, 1), (2, 2)]);
+        assert_eq!(callback_manager.vetoed(), &[HandlerId(12)]);
+        assert!(callback_manager.remove_by_key(&4).unwrap());
+        let _h = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        assert_eq!(callback_manager.active_ids(), vec![HandlerId(10), HandlerId(11), HandlerId(13)]);
+    }
+
+    #[test]
+    fn test_expiry_callback() {
+        let expired = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let expired_clone = Arc::clone(&expired);
+        callback_manager.set_expiry_callback(move |id| expired_clone.lock().unwrap().push(id));
+        let h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        drop(h2);
+        drop(h0);
+        callback_manager.prune();
+        callback_manager.prune();
+        assert_eq!(*expired.lock().unwrap(), vec![HandlerId(0), HandlerId(2)]);
+
+        drop(h3);
+        callback_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+        assert_eq!(*expired.lock().unwrap(), vec![HandlerId(0), HandlerId(2), HandlerId(3)]);
+    }
+
+    #[test]
+    fn test_expiry_callback_borrowing_local() {
+        let mut expired = vec![];
+        {
+            let mut callback_manager = CallbackManager::<i32>::new();
+            callback_manager.set_expiry_callback(|id| expired.push(id));
+            drop(callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+            callback_manager.prune();
+        }
+        assert_eq!(expired, vec![HandlerId(0)]);
+    }
+
+    #[cfg(feature = "fuzz")]
+    #[test]
+    fn test_run_all_never_panics() {
+        // xorshift64, seeded so that failures reproduce.
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        for _ in 0..2000 {
+            let mut callback_manager = CallbackManager::<i32>::new();
+            let mut handles = vec![];
+            let mut accepted = vec![];
+            for _ in 0..next(6) {
+                let handler = match next(4) {
+                    0 => CallbackHandler::CatchAll(Box::new(|_arity, _args| {})),
+                    1 => {
+                        let arities = vec![next(13), next(13)];
+                        accepted.push(arities.clone());
+                        let parts = arities.into_iter().filter_map(CallbackHandler::noop).collect();
+                        CallbackHandler::Multi(MultiArityHandler::new(parts))
+                    },
+                    _ => {
+                        let arity = next(13);
+                        accepted.push(vec![arity]);
+                        CallbackHandler::noop(arity).unwrap()
+                    },
+                };
+                handles.push(callback_manager.add(handler).unwrap());
+            }
+            let params = (0..next(10))
+                .map(|_| CallbackParams::from_array([1; 12]).with_slice(|args| CallbackParams::try_from(&args[..next(13)])).unwrap())
+                .collect::<Vec<_>>();
+
+            let matching = params.len() == accepted.len()
+                && accepted.iter().zip(&params).all(|(arities, param)| arities.contains(&param.arity()));
+            assert_eq!(callback_manager.run_all(params.clone()).is_ok(), matching, "{params:?} against {accepted:?}");
+
+            if !handles.is_empty() {
+                handles.remove(next(handles.len()));
+            }
+            let _ = callback_manager.run_all(params.clone());
+            let _ = callback_manager.run_all_truncate(params.clone());
+            let _ = callback_manager.run_all_cycle(&params);
+            let _ = callback_manager.run_all_with_policy(params.clone(), ParamCountPolicy::AtLeast);
+            let _ = callback_manager.run_all_unchecked(&params);
+        }
+    }
+
+    #[test]
+    fn test_drop_inactive_keeps_live_handlers() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut kept = vec![];
+        for n in 0..1000 {
+            let handle = callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+            if n % 7 == 0 {
+                kept.push(handle);
+            }
+        }
+
+        callback_manager.prune();
+
+        assert_eq!(callback_manager.handlers.len(), kept.len());
+        assert!(callback_manager.handlers.iter().zip(&kept).all(
+            |(entry, handle)| entry.id.0 % 7 == 0 && Arc::ptr_eq(&entry.handler.upgrade().unwrap(), handle)
+        ));
+        assert!(kept.iter().all(|handle| Arc::weak_count(handle) == 1));
+    }
+
+    #[test]
+    fn test_dedupe_within_run() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let handler = Arc::new(sync::Mutex::new(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))));
+        let output2 = Arc::clone(&output);
+        let catch_all = Arc::new(sync::Mutex::new(CallbackHandler::CatchAll(Box::new(move |_arity, args| output2.lock().unwrap().push(args[0] * 10)))));
+        for _ in 0..2 {
+            callback_manager.add_weak(Arc::downgrade(&handler)).unwrap();
+            callback_manager.add_weak(Arc::downgrade(&catch_all)).unwrap();
+        }
+        let params = || vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2)];
+
+        callback_manager.run_all(params()).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![1, 2, 10, 20, 10, 20]);
+
+        output.lock().unwrap().clear();
+        callback_manager.set_dedupe_within_run(true);
+        callback_manager.run_all(params()).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![1, 10, 20]);
+    }
+
+    #[test]
+    fn test_ping_all() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output0 = Arc::clone(&output);
+        let _h0 = callback_manager.add(CallbackHandler::Callback0(Box::new(move || output0.lock().unwrap().push(vec![])))).unwrap();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback3(Box::new(move |x, y, z| output1.lock().unwrap().push(vec![x, y, z])))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(move |_arity, args| output2.lock().unwrap().push(args.to_vec())))).unwrap();
+        let output3 = Arc::clone(&output);
+        let _h3 = callback_manager.add(CallbackHandler::Multi(MultiArityHandler::new(vec![
+            CallbackHandler::Callback1(Box::new(|_x| panic!("not the largest arity"))),
+            CallbackHandler::Callback2(Box::new(move |x, y| output3.lock().unwrap().push(vec![x, y]))),
+        ]))).unwrap();
+        let h4 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| panic!("dropped")))).unwrap();
+        drop(h4);
+
+        assert_eq!(callback_manager.ping_all(), Ok(4));
+        assert_eq!(*output.lock().unwrap(), vec![vec![], vec![0, 0, 0], vec![0, 0], vec![]]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_describe_json() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        drop(callback_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap());
+        callback_manager.track_invocations(4);
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+
+        let json = serde_json::to_value(callback_manager.describe()).unwrap();
+
+        assert_eq!(json, serde_json::json!({
+            "handlers": [
+                { "id": 1, "arity": 1, "catch_all": false, "poisoned": false, "recent_invocations": 1 },
+                { "id": 0, "arity": 0, "catch_all": true, "poisoned": false, "recent_invocations": 1 },
+            ],
+            "frozen": false,
+        }));
+    }
+
+    #[test]
+    fn test_remove() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..3 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
+        }
+
+        assert!(callback_manager.remove(&handles[1]).unwrap());
+        assert_eq!(callback_manager.active_count(), 2);
+        assert!(!callback_manager.remove(&handles[1]).unwrap());
+        callback_manager.run_all(vec![CallbackParams::CallParams1(10), CallbackParams::CallParams1(20)]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec![(0, 10), (2, 20)]);
+        assert_eq!(Arc::strong_count(&handles[1]), 1);
+    }
+
+    #[test]
+    fn test_add_with_id() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut ids = vec![];
+        let mut handles = vec![];
+        for n in 0..3 {
+            let output = Arc::clone(&output);
+            let (id, handle) = callback_manager.add_with_id(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap();
+            ids.push(id);
+            handles.push(handle);
+        }
+        assert_eq!(ids, vec![HandlerId(0), HandlerId(1), HandlerId(2)]);
+
+        assert!(callback_manager.remove_by_id(ids[0]).unwrap());
+        assert!(!callback_manager.remove_by_id(ids[0]).unwrap());
+        let (id, _h3) = callback_manager.add_with_id(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+        assert_eq!(id, HandlerId(3));
+
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams1(2), CallbackParams::CallParams0()]).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![(1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn test_run_one() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| panic!("not this one")))).unwrap();
+        let output1 = Arc::clone(&output);
+        let (id, h1) = callback_manager.add_with_id(CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+
+        callback_manager.run_one(id, CallbackParams::CallParams1(5)).unwrap();
+        assert_eq!(
+            callback_manager.run_one(id, CallbackParams::CallParams2(1, 2)),
+            Err(CallbackError::ParamTypeMismatch { mismatches: vec![ArityMismatch { index: 0, id, expected: 1, got: 2 }] })
+        );
+        assert_eq!(
+            callback_manager.run_one(HandlerId(7), CallbackParams::CallParams1(1)),
+            Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(7)] })
+        );
+        drop(h1);
+        assert_eq!(
+            callback_manager.run_one(id, CallbackParams::CallParams1(1)),
+            Err(CallbackError::UnknownHandlers { ids: vec![id] })
+        );
+        assert_eq!(*output.lock().unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let h1 = callback_manager.add_keyed("first", CallbackHandler::Callback1(Box::new(move |x| output1.lock().unwrap().push(x)))).unwrap();
+        let h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| panic!("cleared")))).unwrap();
+        let h3 = callback_manager.registrar().add(CallbackHandler::Callback0(Box::new(|| panic!("cleared")))).unwrap();
+
+        callback_manager.clear().unwrap();
+
+        assert_eq!(callback_manager.active_count(), 0);
+        assert!(callback_manager.keys.is_empty());
+        assert_eq!((Arc::strong_count(&h1), Arc::strong_count(&h2), Arc::strong_count(&h3)), (1, 1, 1));
+        callback_manager.run_all(vec![]).unwrap();
+
+        let output2 = Arc::clone(&output);
+        let (id, _h4) = callback_manager.add_with_id(CallbackHandler::Callback1(Box::new(move |x| output2.lock().unwrap().push(x)))).unwrap();
+        assert_eq!(id, HandlerId(2));
+        callback_manager.run_all(vec![CallbackParams::CallParams1(1)]).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_tuple_arguments() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<(i32, String)>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |(n, s): (i32, String)| output1.lock().unwrap().push(s.repeat(n as usize))))).unwrap();
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |(n, s), (m, t)| output2.lock().unwrap().push(format!("{}{s}{t}", n + m))))).unwrap();
+
+        callback_manager.run_all_consume(vec![
+            CallbackParams::CallParams1((2, "ab".to_string())),
+            CallbackParams::CallParams2((1, "c".to_string()), (2, "d".to_string())),
+        ]).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec!["abab", "3cd"]);
+    }
+
+    #[test]
+    fn test_run_all_lenient() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let mut handles = vec![];
+        for n in 0..3 {
+            let output = Arc::clone(&output);
+            handles.push(callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output.lock().unwrap().push((n, x))))).unwrap());
         }
+        let output3 = Arc::clone(&output);
+        let _h3 = callback_manager.add(CallbackHandler::CatchAll(Box::new(move |arity, _args| output3.lock().unwrap().push((3, arity as i32))))).unwrap();
+
+        let failures = callback_manager.run_all_lenient(vec![
+            CallbackParams::CallParams1(10),
+            CallbackParams::CallParams2(1, 2),
+            CallbackParams::CallParams1(12),
+        ]);
+        assert_eq!(failures, vec![(1, CallbackError::ParamTypeMismatch {
+            mismatches: vec![ArityMismatch { index: 1, id: HandlerId(1), expected: 1, got: 2 }],
+        })]);
+        assert_eq!(*output.lock().unwrap(), vec![(0, 10), (2, 12), (3, 1), (3, 2), (3, 1)]);
+
+        output.lock().unwrap().clear();
+        let failures = callback_manager.run_all_lenient(vec![CallbackParams::CallParams1(20)]);
+        assert_eq!(failures, vec![(1, CallbackError::ParamCountMismatch { expected: 3, got: 1 })]);
+        assert_eq!(*output.lock().unwrap(), vec![(0, 20), (3, 1)]);
+    }
+
+    #[test]
+    fn test_run_stream_counts_invocations() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let (id, _h0) = callback_manager.add_with_id(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        let _h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        callback_manager.set_validator(id, |args| args[0] >= 0).unwrap();
+
+        let batches = vec![
+            vec![CallbackParams::CallParams1(1)],
+            vec![CallbackParams::CallParams1(-1)],
+        ];
+        assert_eq!(callback_manager.run_stream(batches.into_iter()), Ok(3));
+    }
+
+    #[test]
+    fn test_drive_from_counts_invocations() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let _h0 = callback_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+        let h1 = callback_manager.add(CallbackHandler::CatchAll(Box::new(|_arity, _args| {}))).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        tx.send(vec![CallbackParams::CallParams1(1)]).unwrap();
+        tx.send(vec![CallbackParams::CallParams1(2)]).unwrap();
+        assert_eq!(callback_manager.drive_from(&rx), Ok(4));
+
+        drop(h1);
+        tx.send(vec![CallbackParams::CallParams1(3)]).unwrap();
+        assert_eq!(callback_manager.drive_from(&rx), Ok(1));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,133 @@
+//! Dispatches validated once and run many times.
+
+use crate::{CallbackError, CallbackManager, CallbackParams, DispatchState, HandlerId};
+
+/// Params checked against a manager's handlers by [`CallbackManager::prepare`], to be run repeatedly without
+/// checking their arities again.
+///
+/// The dispatch captures the ids of the positional handlers in their dispatch order at the time it was prepared.
+/// It is invalidated when the handlers change:
+///
+/// - if one of the captured handlers was dropped or removed, `run` fails with `CallbackError::UnknownHandlers`
+///   listing them, and nothing runs;
+/// - handlers added since aren't invoked, except for `CatchAll` handlers which take any params;
+/// - a change of order, for instance from `set_order_by`, is ignored, as the params stay paired with the
+///   captured ids.
+///
+/// Prepare the dispatch again after any of these changes.
+#[derive(Clone, Debug)]
+pub struct Dispatch<T> {
+    ids: Vec<HandlerId>,
+    params: Vec<CallbackParams<T>>,
+}
+
+impl<T> Dispatch<T> {
+    /// Returns the ids of the handlers the params are paired with, in order.
+    pub fn ids(&self) -> &[HandlerId] {
+        &self.ids
+    }
+
+    /// Returns the validated params.
+    pub fn params(&self) -> &[CallbackParams<T>] {
+        &self.params
+    }
+}
+
+impl<'a, T: Copy + 'a> CallbackManager<'a, T> {
+    /// Checks `params` against the active handlers like `run_all` does, and returns a [`Dispatch`] that can run
+    /// them any number of times.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    ///
+    /// let _handler0 = cb_manager.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+    /// let _handler1 = cb_manager.add(CallbackHandler::Callback1(Box::new(|_x| {}))).unwrap();
+    ///
+    /// let dispatch = cb_manager.prepare(vec![CallbackParams::CallParams0(), CallbackParams::CallParams1(1)]).unwrap();
+    /// for _ in 0..3 {
+    ///     assert!(dispatch.run(&cb_manager).is_ok());
+    /// }
+    /// ```
+    pub fn prepare(&self, params: Vec<CallbackParams<T>>) -> Result<Dispatch<T>, CallbackError> {
+        let ids = self.positional_order();
+        if params.len() != ids.len() {
+            return Err(CallbackError::ParamCountMismatch { expected: ids.len(), got: params.len() });
+        }
+
+        let mut mismatches = vec![];
+        for (index, (id, param)) in ids.iter().zip(&params).enumerate() {
+            if let Some(entry) = self.handlers.iter().find(|entry| entry.id == *id) {
+                mismatches.extend(Self::arity_mismatch(index, entry, param)?);
+            }
+        }
+        if !mismatches.is_empty() {
+            return Err(CallbackError::ParamTypeMismatch { mismatches });
+        }
+
+        Ok(Dispatch { ids, params })
+    }
+}
+
+impl<T: Copy> Dispatch<T> {
+    /// Runs the prepared params on `manager`, only checking that the captured handlers are still live.
+    ///
+    /// Like `run_some`, this doesn't trace, notify the observer or forward to chained managers. Validators and
+    /// `CatchAll` handlers run as with `run_all`. The manager should be the one the dispatch was prepared on, as
+    /// ids are only unique within a manager.
+    pub fn run<'a>(&self, manager: &CallbackManager<'a, T>) -> Result<(), CallbackError> where T: 'a {
+        let mut entries = Vec::with_capacity(self.ids.len());
+        let mut unknown = vec![];
+        for id in &self.ids {
+            match manager.handlers.iter().find(|entry| entry.id == *id && entry.handler.strong_count() > 0) {
+                Some(entry) => entries.push(entry),
+                None => unknown.push(*id),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(CallbackError::UnknownHandlers { ids: unknown });
+        }
+
+        let mut state = DispatchState::default();
+        manager.invoke_entries(entries.into_iter().zip(&self.params), &mut state)?;
+        manager.invoke_catch_all(&self.params, &mut state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams, HandlerId};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_prepare() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output0 = Arc::clone(&output);
+        let h0 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| output0.lock().unwrap().push(x)))).unwrap();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback2(Box::new(move |x, y| output1.lock().unwrap().push(x + y)))).unwrap();
+
+        assert!(matches!(
+            callback_manager.prepare(vec![CallbackParams::CallParams2(1, 2), CallbackParams::CallParams1(3)]),
+            Err(CallbackError::ParamTypeMismatch { .. })
+        ));
+        let dispatch = callback_manager.prepare(vec![CallbackParams::CallParams1(1), CallbackParams::CallParams2(2, 3)]).unwrap();
+        assert_eq!(dispatch.ids(), &[HandlerId(0), HandlerId(1)]);
+        dispatch.run(&callback_manager).unwrap();
+        dispatch.run(&callback_manager).unwrap();
+
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(CallbackHandler::CatchAll(Box::new(move |arity, _args| output2.lock().unwrap().push(arity as i32 * 100)))).unwrap();
+        let _h3 = callback_manager.add(CallbackHandler::Callback0(Box::new(|| panic!("not prepared")))).unwrap();
+        dispatch.run(&callback_manager).unwrap();
+        assert_eq!(*output.lock().unwrap(), vec![1, 5, 1, 5, 1, 5, 100, 200]);
+
+        drop(h0);
+        assert_eq!(dispatch.run(&callback_manager), Err(CallbackError::UnknownHandlers { ids: vec![HandlerId(0)] }));
+        assert_eq!(output.lock().unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,147 @@
+//! Handlers borrowing their arguments, for argument types that aren't `Copy`.
+
+use std::sync::Arc;
+
+use crate::entries::{self, Entry};
+use crate::sync::{self, Mutex};
+use crate::{ArityMismatch, CallbackError, CallbackParams, HandlerId};
+
+macro_rules! ref_handlers {
+    ($($variant:ident, $params:ident, $arity:literal, ($($p:ident: $t:ident),*);)*) => {
+        /// Enumeration of handlers taking their arguments by reference, registered with a [`RefCallbackManager`].
+        #[allow(clippy::type_complexity)]
+        pub enum RefCallbackHandler<'a, T: 'a> {
+            $($variant(Box<dyn FnMut($(&$t),*) + Send + 'a>),)*
+        }
+
+        impl<'a, T: 'a> RefCallbackHandler<'a, T> {
+            /// Returns the number of arguments the handler takes.
+            pub const fn arity(&self) -> usize {
+                match self {
+                    $(RefCallbackHandler::$variant(_) => $arity,)*
+                }
+            }
+
+            /// Invokes the handler with references to the params' arguments, returning `false` without invoking it
+            /// if the arities differ.
+            fn call(&mut self, params: &CallbackParams<T>) -> bool {
+                match (self, params) {
+                    $((RefCallbackHandler::$variant(handler), CallbackParams::$params($($p),*)) => handler($($p),*),)*
+                    _ => return false,
+                }
+                true
+            }
+        }
+    };
+}
+
+ref_handlers! {
+    Callback0, CallParams0, 0, ();
+    Callback1, CallParams1, 1, (p1: T);
+    Callback2, CallParams2, 2, (p1: T, p2: T);
+    Callback3, CallParams3, 3, (p1: T, p2: T, p3: T);
+    Callback4, CallParams4, 4, (p1: T, p2: T, p3: T, p4: T);
+    Callback5, CallParams5, 5, (p1: T, p2: T, p3: T, p4: T, p5: T);
+    Callback6, CallParams6, 6, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T);
+    Callback7, CallParams7, 7, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T);
+    Callback8, CallParams8, 8, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T);
+    Callback9, CallParams9, 9, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T);
+    Callback10, CallParams10, 10, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T);
+    Callback11, CallParams11, 11, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T);
+    Callback12, CallParams12, 12, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T, p12: T);
+}
+
+/// A manager of [`RefCallbackHandler`]s, dispatching params by reference so that arguments don't need to be `Copy`.
+///
+/// [`CallbackManager::run_all_consume`](crate::CallbackManager::run_all_consume) also accepts arguments that aren't
+/// `Copy`, but moves them into the handlers. This manager keeps them borrowed instead, so the same params can be
+/// dispatched again. As with `CallbackManager`, the manager only holds weak references: a handler stays registered
+/// as long as the handle returned by `add` is alive.
+pub struct RefCallbackManager<'a, T: 'a> {
+    handlers: Vec<Entry<RefCallbackHandler<'a, T>>>,
+    next_id: u64,
+}
+
+impl<'a, T: 'a> Default for RefCallbackManager<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: 'a> RefCallbackManager<'a, T> {
+    /// Creates a new `RefCallbackManager` instance.
+    pub fn new() -> Self {
+        Self { handlers: vec![], next_id: 0 }
+    }
+
+    /// Adds a new handler, returning the strong handle keeping it registered.
+    pub fn add(&mut self, handler: RefCallbackHandler<'a, T>) -> Arc<Mutex<RefCallbackHandler<'a, T>>> {
+        let strong_handler = Arc::new(Mutex::new(handler));
+        self.handlers.push((HandlerId(self.next_id), Arc::downgrade(&strong_handler)));
+        self.next_id += 1;
+        strong_handler
+    }
+
+    /// Returns the number of handlers still alive.
+    pub fn active_count(&self) -> usize {
+        self.handlers.iter().filter(|(_, handler)| handler.strong_count() > 0).count()
+    }
+
+    /// Runs all active handlers with specific parameter lists, passing each handler references to the arguments
+    /// of its param.
+    ///
+    /// As with `CallbackManager::run_all`, the params are checked against the handlers before any is invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackParams, RefCallbackHandler, RefCallbackManager};
+    ///
+    /// let mut cb_manager = RefCallbackManager::<String>::new();
+    ///
+    /// let _handler = cb_manager.add(RefCallbackHandler::Callback1(Box::new(|s| println!("{s}"))));
+    ///
+    /// let params = vec![CallbackParams::CallParams1("borrowed".to_string())];
+    /// assert!(cb_manager.run_all_ref(&params).is_ok());
+    /// assert!(cb_manager.run_all_ref(&params).is_ok());
+    /// ```
+    pub fn run_all_ref(&mut self, params: &[CallbackParams<T>]) -> Result<(), CallbackError> {
+        entries::prune_and_check(&mut self.handlers, params, RefCallbackHandler::arity)?;
+
+        for (index, ((id, handler), param)) in self.handlers.iter().zip(params).enumerate() {
+            let mutex_handler = handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+            let mut guard_handler = sync::lock(&mutex_handler)?;
+            if !guard_handler.call(param) {
+                let mismatch = ArityMismatch { index, id: *id, expected: guard_handler.arity(), got: param.arity() };
+                return Err(CallbackError::ParamTypeMismatch { mismatches: vec![mismatch] });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackParams, RefCallbackHandler, RefCallbackManager};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_run_all_ref() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = RefCallbackManager::<String>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(RefCallbackHandler::Callback1(Box::new(move |s| output1.lock().unwrap().push(s.clone()))));
+        let output2 = Arc::clone(&output);
+        let _h2 = callback_manager.add(RefCallbackHandler::Callback2(Box::new(move |s, t| output2.lock().unwrap().push(format!("{s} {t}")))));
+
+        let params = vec![
+            CallbackParams::CallParams1("one".to_string()),
+            CallbackParams::CallParams2("two".to_string(), "three".to_string()),
+        ];
+        callback_manager.run_all_ref(&params).unwrap();
+        callback_manager.run_all_ref(&params).unwrap();
+
+        assert_eq!(*output.lock().unwrap(), vec!["one", "two three", "one", "two three"]);
+        assert!(matches!(callback_manager.run_all_ref(&params[..1]), Err(CallbackError::ParamCountMismatch { expected: 2, got: 1 })));
+    }
+}
@@ -0,0 +1,128 @@
+//! Registering handlers while the manager is borrowed elsewhere, for example by a dispatch on another thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+
+use crate::sync::Mutex;
+use crate::{CallbackError, CallbackHandler, CallbackManager};
+
+/// Handlers added through a [`Registrar`] and not yet registered with its manager.
+pub(crate) type PendingHandlers<'a, T> = Arc<std::sync::Mutex<Vec<Weak<Mutex<CallbackHandler<'a, T>>>>>>;
+
+/// A handle for adding handlers to a manager without borrowing it, returned by [`CallbackManager::registrar`].
+///
+/// Adding through the registrar only takes `&self` and a short lock on a queue of its own, so it doesn't contend
+/// with a dispatch in progress. The queued handlers are registered, in the order they were added, at the start of
+/// the manager's next dispatch, so a handler added while a dispatch is running first runs on the following one.
+/// Clones share the same queue.
+pub struct Registrar<'a, T: 'a> {
+    pending: PendingHandlers<'a, T>,
+    frozen: Arc<AtomicBool>,
+}
+
+impl<'a, T: 'a> Registrar<'a, T> {
+    /// Queues a new callback handler for registration at the start of the manager's next dispatch.
+    ///
+    /// As with `CallbackManager::add`, the handler stays registered only while the returned handle is alive, and
+    /// this fails with `CallbackError::Frozen` once the manager is frozen. Handlers queued before the freeze are
+    /// still registered.
+    pub fn add(&self, handler: CallbackHandler<'a, T>) -> Result<Arc<Mutex<CallbackHandler<'a, T>>>, CallbackError> {
+        let mut pending = self.pending.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if self.frozen.load(Ordering::Relaxed) {
+            return Err(CallbackError::Frozen);
+        }
+        let strong_handler = Arc::new(Mutex::new(handler));
+        pending.push(Arc::downgrade(&strong_handler));
+        Ok(strong_handler)
+    }
+}
+
+impl<'a, T: 'a> Clone for Registrar<'a, T> {
+    fn clone(&self) -> Self {
+        Self { pending: Arc::clone(&self.pending), frozen: Arc::clone(&self.frozen) }
+    }
+}
+
+impl<'a, T: 'a> CallbackManager<'a, T> {
+    /// Returns a registrar adding handlers to this manager through a shared queue, see [`Registrar`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let registrar = cb_manager.registrar();
+    ///
+    /// let _handler = std::thread::spawn(move || {
+    ///     registrar.add(CallbackHandler::Callback1(Box::new(|x| println!("number is {x}"))))
+    /// }).join().unwrap().unwrap();
+    /// assert_eq!(cb_manager.active_count(), 0);
+    ///
+    /// assert!(cb_manager.run_all(vec![CallbackParams::CallParams1(1)]).is_ok());
+    /// assert_eq!(cb_manager.active_count(), 1);
+    /// ```
+    pub fn registrar(&self) -> Registrar<'a, T> {
+        Registrar { pending: Arc::clone(&self.pending), frozen: Arc::clone(&self.frozen) }
+    }
+
+    /// Registers the handlers queued through registrars.
+    pub(crate) fn flush_pending(&mut self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(std::sync::PoisonError::into_inner));
+        for weak in pending {
+            self.register_weak(weak);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams, ParamCountPolicy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_registrar_stress() {
+        const THREADS: usize = 4;
+        const HANDLERS: usize = 100;
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let mut callback_manager = CallbackManager::<i32>::new();
+
+        let adders = (0..THREADS).map(|_| {
+            let (registrar, invocations) = (callback_manager.registrar(), Arc::clone(&invocations));
+            thread::spawn(move || {
+                (0..HANDLERS).map(|_| {
+                    let invocations = Arc::clone(&invocations);
+                    registrar.add(CallbackHandler::Callback0(Box::new(move || { invocations.fetch_add(1, Ordering::Relaxed); }))).unwrap()
+                }).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+        let params = vec![CallbackParams::CallParams0(); THREADS * HANDLERS];
+        while !adders.iter().all(|adder| adder.is_finished()) {
+            callback_manager.run_all_with_policy(params.clone(), ParamCountPolicy::AtLeast).unwrap();
+            assert!(callback_manager.active_count() <= THREADS * HANDLERS);
+        }
+        let _handlers = adders.into_iter().map(|adder| adder.join().unwrap()).collect::<Vec<_>>();
+
+        let before = invocations.load(Ordering::Relaxed);
+        callback_manager.run_all(params).unwrap();
+        assert_eq!(callback_manager.active_count(), THREADS * HANDLERS);
+        assert_eq!(invocations.load(Ordering::Relaxed) - before, THREADS * HANDLERS);
+        assert_eq!(callback_manager.active_ids().len(), THREADS * HANDLERS);
+    }
+
+    #[test]
+    fn test_registrar_frozen() {
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let registrar = callback_manager.registrar();
+        let _h0 = registrar.add(CallbackHandler::Callback0(Box::new(|| {}))).unwrap();
+
+        callback_manager.freeze();
+
+        assert_eq!(callback_manager.active_count(), 1);
+        assert_eq!(registrar.clone().add(CallbackHandler::Callback0(Box::new(|| {}))).err(), Some(CallbackError::Frozen));
+        callback_manager.run_all(vec![CallbackParams::CallParams0()]).unwrap();
+        assert_eq!(callback_manager.active_count(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Handlers returning a value, collected by the dispatch.
+
+use std::sync::Arc;
+
+use crate::entries::{self, Entry};
+use crate::sync::{self, Mutex};
+use crate::{ArityMismatch, CallbackError, CallbackParams, HandlerId};
+
+macro_rules! returning_handlers {
+    ($($variant:ident, $params:ident, $arity:literal, ($($p:ident: $t:ident),*);)*) => {
+        /// Enumeration of handlers returning a value of type `R`, registered with a [`ReturningCallbackManager`].
+        #[allow(clippy::type_complexity)]
+        pub enum ReturningCallbackHandler<'a, T: 'a, R: 'a = T> {
+            $($variant(Box<dyn FnMut($($t),*) -> R + Send + 'a>),)*
+        }
+
+        impl<'a, T: 'a, R: 'a> ReturningCallbackHandler<'a, T, R> {
+            /// Returns the number of arguments the handler takes.
+            pub const fn arity(&self) -> usize {
+                match self {
+                    $(ReturningCallbackHandler::$variant(_) => $arity,)*
+                }
+            }
+        }
+
+        impl<'a, T: Copy + 'a, R: 'a> ReturningCallbackHandler<'a, T, R> {
+            /// Invokes the handler with the params, returning its value, or `None` if the arities differ.
+            fn call(&mut self, params: &CallbackParams<T>) -> Option<R> {
+                match (self, params) {
+                    $((ReturningCallbackHandler::$variant(handler), CallbackParams::$params($($p),*)) => Some(handler($(*$p),*)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+returning_handlers! {
+    Callback0, CallParams0, 0, ();
+    Callback1, CallParams1, 1, (p1: T);
+    Callback2, CallParams2, 2, (p1: T, p2: T);
+    Callback3, CallParams3, 3, (p1: T, p2: T, p3: T);
+    Callback4, CallParams4, 4, (p1: T, p2: T, p3: T, p4: T);
+    Callback5, CallParams5, 5, (p1: T, p2: T, p3: T, p4: T, p5: T);
+    Callback6, CallParams6, 6, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T);
+    Callback7, CallParams7, 7, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T);
+    Callback8, CallParams8, 8, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T);
+    Callback9, CallParams9, 9, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T);
+    Callback10, CallParams10, 10, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T);
+    Callback11, CallParams11, 11, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T);
+    Callback12, CallParams12, 12, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T, p12: T);
+}
+
+/// A manager of [`ReturningCallbackHandler`]s, whose `run_all` collects the value returned by each handler.
+///
+/// As with [`CallbackManager`](crate::CallbackManager), the manager only holds weak references: a handler stays
+/// registered as long as the handle returned by `add` is alive.
+pub struct ReturningCallbackManager<'a, T: 'a, R: 'a = T> {
+    handlers: Vec<Entry<ReturningCallbackHandler<'a, T, R>>>,
+    next_id: u64,
+}
+
+impl<'a, T: 'a, R: 'a> Default for ReturningCallbackManager<'a, T, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: 'a, R: 'a> ReturningCallbackManager<'a, T, R> {
+    /// Creates a new `ReturningCallbackManager` instance.
+    pub fn new() -> Self {
+        Self { handlers: vec![], next_id: 0 }
+    }
+
+    /// Adds a new returning handler, returning the strong handle keeping it registered.
+    pub fn add(&mut self, handler: ReturningCallbackHandler<'a, T, R>) -> Arc<Mutex<ReturningCallbackHandler<'a, T, R>>> {
+        let strong_handler = Arc::new(Mutex::new(handler));
+        self.handlers.push((HandlerId(self.next_id), Arc::downgrade(&strong_handler)));
+        self.next_id += 1;
+        strong_handler
+    }
+
+    /// Returns the number of handlers still alive.
+    pub fn active_count(&self) -> usize {
+        self.handlers.iter().filter(|(_, handler)| handler.strong_count() > 0).count()
+    }
+}
+
+impl<'a, T: Copy + 'a, R: 'a> ReturningCallbackManager<'a, T, R> {
+    /// Runs all active handlers with specific parameter lists, and returns their values in registration order.
+    ///
+    /// As with `CallbackManager::run_all`, the params are checked against the handlers before any is invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use callback_manager::{CallbackParams, ReturningCallbackHandler, ReturningCallbackManager};
+    ///
+    /// let mut cb_manager = ReturningCallbackManager::<i32>::new();
+    ///
+    /// let _sum = cb_manager.add(ReturningCallbackHandler::Callback2(Box::new(|x, y| x + y)));
+    /// let _square = cb_manager.add(ReturningCallbackHandler::Callback1(Box::new(|x| x * x)));
+    ///
+    /// let results = cb_manager.run_all(vec![CallbackParams::CallParams2(1, 2), CallbackParams::CallParams1(4)]);
+    /// assert_eq!(results, Ok(vec![3, 16]));
+    /// ```
+    pub fn run_all(&mut self, params: Vec<CallbackParams<T>>) -> Result<Vec<R>, CallbackError> {
+        entries::prune_and_check(&mut self.handlers, &params, ReturningCallbackHandler::arity)?;
+
+        let mut results = Vec::with_capacity(params.len());
+        for (index, ((id, handler), param)) in self.handlers.iter().zip(&params).enumerate() {
+            let mutex_handler = handler.upgrade().ok_or(CallbackError::HandlerDropped)?;
+            let mut guard_handler = sync::lock(&mutex_handler)?;
+            let arity = guard_handler.arity();
+            results.push(guard_handler.call(param).ok_or_else(|| CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index, id: *id, expected: arity, got: param.arity() }],
+            })?);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArityMismatch, CallbackError, CallbackParams, HandlerId, ReturningCallbackHandler, ReturningCallbackManager};
+
+    #[test]
+    fn test_returning_run_all() {
+        let mut callback_manager = ReturningCallbackManager::<i32, String>::new();
+        let _h0 = callback_manager.add(ReturningCallbackHandler::Callback2(Box::new(|x, y| format!("{x}+{y}"))));
+        let mut calls = 0;
+        let _h1 = callback_manager.add(ReturningCallbackHandler::Callback0(Box::new(move || {
+            calls += 1;
+            format!("call {calls}")
+        })));
+        let h2 = callback_manager.add(ReturningCallbackHandler::Callback1(Box::new(|x| x.to_string())));
+        drop(h2);
+
+        let params = || vec![CallbackParams::CallParams2(1, 2), CallbackParams::CallParams0()];
+        assert_eq!(callback_manager.run_all(params()), Ok(vec!["1+2".to_string(), "call 1".to_string()]));
+        assert_eq!(callback_manager.run_all(params()), Ok(vec!["1+2".to_string(), "call 2".to_string()]));
+        assert_eq!(
+            callback_manager.run_all(vec![CallbackParams::CallParams0(), CallbackParams::CallParams0()]),
+            Err(CallbackError::ParamTypeMismatch {
+                mismatches: vec![ArityMismatch { index: 0, id: HandlerId(0), expected: 2, got: 0 }],
+            })
+        );
+    }
+}
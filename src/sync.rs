@@ -0,0 +1,48 @@
+//! The mutex guarding each handler.
+//!
+//! This is `std::sync::Mutex` by default, or `parking_lot::Mutex` with the `parking_lot` feature. The two differ in
+//! whether `lock` can fail, so the crate locks handlers through the functions here rather than calling `lock` directly.
+
+#[cfg(not(feature = "parking_lot"))]
+pub use std::sync::{Mutex, MutexGuard};
+
+#[cfg(feature = "parking_lot")]
+pub use parking_lot::{Mutex, MutexGuard};
+
+use crate::CallbackError;
+
+/// Locks `mutex`, failing if a panicking callback poisoned it.
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, CallbackError> {
+    mutex.lock().map_err(|_| CallbackError::LockPoisoned)
+}
+
+/// Locks `mutex`. `parking_lot` mutexes never poison, so this never fails.
+#[cfg(feature = "parking_lot")]
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, CallbackError> {
+    Ok(mutex.lock())
+}
+
+/// Locks `mutex`, even if a panicking callback poisoned it.
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) fn lock_ignoring_poison<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Locks `mutex`.
+#[cfg(feature = "parking_lot")]
+pub(crate) fn lock_ignoring_poison<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock()
+}
+
+/// Returns whether a panicking callback poisoned `mutex`.
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) fn is_poisoned<T: ?Sized>(mutex: &Mutex<T>) -> bool {
+    mutex.is_poisoned()
+}
+
+/// Always false, as `parking_lot` mutexes never poison.
+#[cfg(feature = "parking_lot")]
+pub(crate) fn is_poisoned<T: ?Sized>(_mutex: &Mutex<T>) -> bool {
+    false
+}
@@ -0,0 +1,115 @@
+//! Typed handles for handlers registered with a known arity.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::sync::{self, Mutex};
+use crate::{ArityMismatch, CallbackError, CallbackHandler, CallbackManager, HandlerId};
+
+/// A strong handle to a handler whose arity is known at compile time.
+///
+/// Returned by the typed constructors such as [`CallbackManager::add2`]. Like the `Arc` returned by
+/// [`CallbackManager::add`], the handler stays registered only as long as the handle is alive.
+///
+/// `call` still locks the handler's shared `Mutex`, so it never runs concurrently with a dispatch of the same handler.
+pub struct TypedHandle<'a, A, T: Copy + 'a> {
+    id: HandlerId,
+    handler: Arc<Mutex<CallbackHandler<'a, T>>>,
+    arity: PhantomData<A>,
+}
+
+impl<'a, A, T: Copy + 'a> TypedHandle<'a, A, T> {
+    /// Returns the id the handler was registered with.
+    pub fn id(&self) -> HandlerId {
+        self.id
+    }
+
+    /// Returns the underlying handler.
+    pub fn handler(&self) -> &Arc<Mutex<CallbackHandler<'a, T>>> {
+        &self.handler
+    }
+
+    fn mismatch(&self, expected: usize, got: usize) -> CallbackError {
+        CallbackError::ParamTypeMismatch { mismatches: vec![ArityMismatch { index: 0, id: self.id, expected, got }] }
+    }
+}
+
+macro_rules! typed_handles {
+    ($($arity_type:ident, $add:ident, $variant:ident, $arity:literal, ($($p:ident: $t:ident),*);)*) => {
+        $(
+            #[doc = concat!("Marker type for handlers taking ", stringify!($arity), " arguments.")]
+            pub struct $arity_type;
+
+            impl<'a, T: Copy + 'a> CallbackManager<'a, T> {
+                #[doc = concat!("Adds a handler taking ", stringify!($arity), " arguments and returns a typed handle to it.")]
+                ///
+                /// See [`TypedHandle`]. Fails with `CallbackError::Frozen` if the manager is frozen.
+                pub fn $add(&mut self, handler: impl FnMut($($t),*) + Send + 'a) -> Result<TypedHandle<'a, $arity_type, T>, CallbackError> {
+                    let (id, handler) = self.add_with_id(CallbackHandler::$variant(Box::new(handler)))?;
+                    Ok(TypedHandle { id, handler, arity: PhantomData })
+                }
+            }
+
+            impl<'a, T: Copy + 'a> TypedHandle<'a, $arity_type, T> {
+                /// Locks the handler and invokes it directly, without matching a `CallbackParams`.
+                ///
+                /// Fails if the handler's mutex is poisoned, or if the handler was replaced through another handle
+                /// by one of a different arity.
+                #[allow(clippy::too_many_arguments)]
+                pub fn call(&self, $($p: $t),*) -> Result<(), CallbackError> {
+                    let mut guard_handler = sync::lock(&self.handler)?;
+                    match &mut *guard_handler {
+                        CallbackHandler::$variant(handler) => {
+                            handler($($p),*);
+                            Ok(())
+                        },
+                        other => Err(self.mismatch(other.arity(), $arity)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+typed_handles! {
+    Arity0, add0, Callback0, 0, ();
+    Arity1, add1, Callback1, 1, (p1: T);
+    Arity2, add2, Callback2, 2, (p1: T, p2: T);
+    Arity3, add3, Callback3, 3, (p1: T, p2: T, p3: T);
+    Arity4, add4, Callback4, 4, (p1: T, p2: T, p3: T, p4: T);
+    Arity5, add5, Callback5, 5, (p1: T, p2: T, p3: T, p4: T, p5: T);
+    Arity6, add6, Callback6, 6, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T);
+    Arity7, add7, Callback7, 7, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T);
+    Arity8, add8, Callback8, 8, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T);
+    Arity9, add9, Callback9, 9, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T);
+    Arity10, add10, Callback10, 10, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T);
+    Arity11, add11, Callback11, 11, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T);
+    Arity12, add12, Callback12, 12, (p1: T, p2: T, p3: T, p4: T, p5: T, p6: T, p7: T, p8: T, p9: T, p10: T, p11: T, p12: T);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackManager, CallbackParams};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_typed_handle() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+
+        let output2 = Arc::clone(&output);
+        let h2 = callback_manager.add2(move |x, y| output2.lock().unwrap().push(x * y)).unwrap();
+        let output0 = Arc::clone(&output);
+        let h0 = callback_manager.add0(move || output0.lock().unwrap().push(0)).unwrap();
+
+        h2.call(3, 4).unwrap();
+        h0.call().unwrap();
+        callback_manager.run_all(vec![CallbackParams::CallParams2(5, 6), CallbackParams::CallParams0()]).unwrap();
+
+        assert_eq!(h0.id(), callback_manager.handlers[1].id);
+        assert_eq!(*output.lock().unwrap(), vec![12, 0, 30, 0]);
+
+        drop(h2);
+        assert_eq!(callback_manager.active_count(), 1);
+    }
+}
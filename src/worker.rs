@@ -0,0 +1,115 @@
+//! Running a manager's callbacks on a dedicated worker thread.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::{CallbackError, CallbackManager, CallbackParams};
+
+type Job<T> = (Vec<CallbackParams<T>>, Sender<Result<(), CallbackError>>);
+
+/// A handle to a manager moved onto its own thread by [`CallbackManager::spawn_worker`].
+///
+/// Dropping the handle shuts the worker down, waiting for the params already sent to be dispatched.
+pub struct WorkerHandle<T: Copy + Send + 'static> {
+    jobs: Option<Sender<Job<T>>>,
+    thread: Option<JoinHandle<CallbackManager<'static, T>>>,
+}
+
+impl<T: Copy + Send + 'static> CallbackManager<'static, T> {
+    /// Moves the manager onto a new thread that runs `run_all` for every params batch sent through the returned
+    /// handle, in the order they are sent.
+    ///
+    /// The manager still only holds weak references, so the handles returned when adding handlers must be kept
+    /// alive for the handlers to stay registered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use callback_manager::{CallbackHandler, CallbackManager, CallbackParams};
+    ///
+    /// let mut cb_manager = CallbackManager::<i32>::new();
+    /// let sum = Arc::new(Mutex::new(0));
+    ///
+    /// let sum_clone = Arc::clone(&sum);
+    /// let _handler = cb_manager.add(CallbackHandler::Callback1(Box::new(move |x| *sum_clone.lock().unwrap() += x))).unwrap();
+    ///
+    /// let worker = cb_manager.spawn_worker();
+    /// let result = worker.dispatch(vec![CallbackParams::CallParams1(2)]);
+    /// assert_eq!(result.recv().unwrap(), Ok(()));
+    ///
+    /// let _cb_manager = worker.shutdown();
+    /// assert_eq!(*sum.lock().unwrap(), 2);
+    /// ```
+    pub fn spawn_worker(mut self) -> WorkerHandle<T> {
+        let (jobs, rx) = mpsc::channel::<Job<T>>();
+        let thread = thread::spawn(move || {
+            for (params, result) in rx {
+                // The caller may not wait for the result.
+                let _ = result.send(self.run_all(params));
+            }
+            self
+        });
+        WorkerHandle { jobs: Some(jobs), thread: Some(thread) }
+    }
+}
+
+impl<T: Copy + Send + 'static> WorkerHandle<T> {
+    /// Sends params to the worker, returning a receiver for the result of their `run_all` call.
+    ///
+    /// If the worker thread has died, the receiver reports a disconnection instead of a result.
+    pub fn dispatch(&self, params: Vec<CallbackParams<T>>) -> Receiver<Result<(), CallbackError>> {
+        let (result, rx) = mpsc::channel();
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send((params, result));
+        }
+        rx
+    }
+
+    /// Stops the worker once the params already sent are dispatched, and returns the manager.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of a callback that panicked on the worker thread.
+    pub fn shutdown(mut self) -> CallbackManager<'static, T> {
+        self.jobs = None;
+        let thread = self.thread.take().expect("worker thread is only taken on shutdown");
+        thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl<T: Copy + Send + 'static> Drop for WorkerHandle<T> {
+    fn drop(&mut self) {
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CallbackError, CallbackHandler, CallbackManager, CallbackParams};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_worker() {
+        let output = Arc::new(Mutex::new(vec![]));
+        let mut callback_manager = CallbackManager::<i32>::new();
+        let output1 = Arc::clone(&output);
+        let _h1 = callback_manager.add(CallbackHandler::Callback1(Box::new(move |x| {
+            output1.lock().unwrap().push((std::thread::current().id(), x));
+        }))).unwrap();
+
+        let worker = callback_manager.spawn_worker();
+        let results = (0..3).map(|x| worker.dispatch(vec![CallbackParams::CallParams1(x)])).collect::<Vec<_>>();
+        let mismatch = worker.dispatch(vec![]);
+        drop(worker);
+
+        assert!(results.iter().all(|result| result.recv() == Ok(Ok(()))));
+        assert_eq!(mismatch.recv(), Ok(Err(CallbackError::ParamCountMismatch { expected: 1, got: 0 })));
+        let output = output.lock().unwrap();
+        assert_eq!(output.iter().map(|(_, x)| *x).collect::<Vec<i32>>(), vec![0, 1, 2]);
+        assert!(output.iter().all(|(thread, _)| *thread != std::thread::current().id()));
+    }
+}